@@ -0,0 +1,68 @@
+//! Compares the dispatch of a hand-written implementation with the default and `lock_free`
+//! anonymous implementations. Run with `cargo bench --bench lock_free`.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+trait Scale {
+    fn scale(&self, value: u64) -> u64;
+}
+
+struct HandWritten {
+    factor: u64,
+}
+
+impl Scale for HandWritten {
+    fn scale(&self, value: u64) -> u64 {
+        value * self.factor
+    }
+}
+
+const ITERATIONS: u64 = 10_000_000;
+
+fn run(scale: &impl Scale) -> Duration {
+    let start = Instant::now();
+    let mut sum = 0u64;
+    for i in 0..ITERATIONS {
+        sum = sum.wrapping_add(scale.scale(black_box(i)));
+    }
+    black_box(sum);
+    start.elapsed()
+}
+
+fn report(name: &str, elapsed: Duration) {
+    println!(
+        "{:<12} {:>8.3} ns/call",
+        name,
+        elapsed.as_nanos() as f64 / ITERATIONS as f64
+    );
+}
+
+fn main() {
+    let factor = 3;
+
+    let hand_written = HandWritten { factor };
+
+    #[anonymous_trait::anonymous_trait(let locked = 0u8)]
+    impl Scale for u8 {
+        fn scale(&self, value: u64) -> u64 {
+            value * factor
+        }
+    }
+
+    #[anonymous_trait::anonymous_trait(let lock_free = 0u8, lock_free)]
+    impl Scale for u8 {
+        fn scale(&self, value: u64) -> u64 {
+            value * factor
+        }
+    }
+
+    // warm up
+    run(&hand_written);
+
+    report("hand-written", run(&hand_written));
+    report("locked", run(&locked));
+    report("lock_free", run(&lock_free));
+}
@@ -0,0 +1,44 @@
+trait Cat {
+    async fn meow(&self) -> String;
+    async fn set_name(&mut self, new: String);
+    fn name(&self) -> String;
+}
+
+async fn fetch_name() -> String {
+    "remote".to_string()
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut names = vec![];
+
+    #[anonymous_trait::anonymous_trait(let mut cat_mock = String::from("default"), async_closures)]
+    impl Cat for String {
+        async fn meow(&self) -> String {
+            let remote = fetch_name().await;
+            tokio::task::yield_now().await;
+            format!("meow from {}@{}", self, remote)
+        }
+
+        async fn set_name(&mut self, new: String) {
+            tokio::task::yield_now().await;
+            names.push(new.clone());
+            *self = new;
+        }
+
+        fn name(&self) -> String {
+            self.clone()
+        }
+    }
+
+    run(&mut cat_mock).await;
+    println!("names: {names:?}, expected: [\"hi\"]");
+}
+
+async fn run(cat: &mut impl Cat) {
+    cat.set_name("hi".to_string()).await;
+    println!("name: {}, expected: hi", cat.name());
+    // `&self` methods are not locked, so concurrent calls interleave at the awaits.
+    let (first, second) = tokio::join!(cat.meow(), cat.meow());
+    println!("meows: {first}, {second}, expected: meow from hi@remote, meow from hi@remote");
+}
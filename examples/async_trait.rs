@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+
+#[async_trait]
+trait Repository: Send + Sync {
+    async fn find(&self, id: u32) -> Option<String>;
+    async fn save(&mut self, id: u32, name: String);
+}
+
+#[async_trait(?Send)]
+trait LocalRepository {
+    async fn find(&self, id: u32) -> Option<String>;
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let prefix = "user";
+
+    // `#[anonymous_trait]` must be placed above `#[async_trait]`.
+    #[anonymous_trait::anonymous_trait(let mut repository = Vec::<(u32, String)>::new())]
+    #[async_trait]
+    impl Repository for Vec<(u32, String)> {
+        async fn find(&self, id: u32) -> Option<String> {
+            self.iter()
+                .find(|(key, _)| *key == id)
+                .map(|(_, name)| format!("{prefix}:{name}"))
+        }
+
+        async fn save(&mut self, id: u32, name: String) {
+            self.push((id, name));
+        }
+    }
+
+    repository.save(1, "alice".into()).await;
+    println!(
+        "find: {:?}, expected: Some(\"user:alice\")",
+        repository.find(1).await
+    );
+    println!("dyn: {:?}, expected: None", find_dyn(&repository).await);
+
+    let local = std::rc::Rc::new("local");
+
+    #[anonymous_trait::anonymous_trait(let local_repository = ())]
+    #[async_trait(?Send)]
+    impl LocalRepository for () {
+        async fn find(&self, id: u32) -> Option<String> {
+            Some(format!("{local}:{id}"))
+        }
+    }
+
+    println!(
+        "local: {:?}, expected: Some(\"local:2\")",
+        local_repository.find(2).await
+    );
+}
+
+async fn find_dyn(repository: &dyn Repository) -> Option<String> {
+    repository.find(2).await
+}
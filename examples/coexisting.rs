@@ -0,0 +1,43 @@
+mod a {
+    pub trait Store {
+        fn get(&self) -> String;
+    }
+}
+
+mod b {
+    pub trait Store {
+        fn get(&self) -> u32;
+    }
+}
+
+fn main() {
+    let name = "first";
+
+    #[anonymous_trait::anonymous_trait(let store = ())]
+    impl a::Store for () {
+        fn get(&self) -> String {
+            name.to_string()
+        }
+    }
+
+    let first = a::Store::get(&store);
+
+    #[anonymous_trait::anonymous_trait(let store = ())]
+    impl a::Store for () {
+        fn get(&self) -> String {
+            "second".to_string()
+        }
+    }
+
+    println!("first: {first}, expected: first");
+    println!("second: {}, expected: second", a::Store::get(&store));
+
+    #[anonymous_trait::anonymous_trait(let store = 42u32)]
+    impl b::Store for u32 {
+        fn get(&self) -> u32 {
+            *self
+        }
+    }
+
+    println!("b::Store: {}, expected: 42", b::Store::get(&store));
+}
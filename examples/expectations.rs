@@ -0,0 +1,41 @@
+trait Cat {
+    fn meow(&self) -> String;
+    fn set_name(&mut self, new: String);
+}
+
+fn main() {
+    let mut heard = vec![];
+
+    #[anonymous_trait::anonymous_trait(
+        let mut cat_mock = String::from("default"),
+        meow: times(2),
+        set_name: never
+    )]
+    impl Cat for String {
+        fn meow(&self) -> String {
+            heard.push(self.clone());
+            self.clone()
+        }
+
+        fn set_name(&mut self, new: String) {
+            *self = new;
+        }
+    }
+
+    run(&mut cat_mock, None);
+    println!(
+        "meow called {} time(s), expected: 2",
+        cat_mock.meow_call_count()
+    );
+    // the expectations are verified here, which ends the borrow of `heard`
+    cat_mock.verify_expectations();
+    println!("heard: {heard:?}, expected: [\"default\", \"default\"]");
+}
+
+fn run(cat: &mut impl Cat, new_name: Option<String>) {
+    cat.meow();
+    if let Some(new_name) = new_name {
+        cat.set_name(new_name);
+    }
+    cat.meow();
+}
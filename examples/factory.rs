@@ -0,0 +1,53 @@
+trait Connection {
+    fn query(&mut self, sql: &str) -> String;
+    fn id(&self) -> u32;
+}
+
+fn main() {
+    let mut log = vec![];
+
+    #[anonymous_trait::anonymous_trait(let make_conn = _, factory, spy)]
+    impl Connection for u32 {
+        fn query(&mut self, sql: &str) -> String {
+            log.push(format!("{}: {}", self, sql));
+            format!("result of {} on {}", sql, self)
+        }
+
+        fn id(&self) -> u32 {
+            *self
+        }
+    }
+
+    let mut a = make_conn(1);
+    let mut b = make_conn(2);
+    println!("a: {}, expected: result of select on 1", a.query("select"));
+    println!("b: {}, expected: result of insert on 2", b.query("insert"));
+    println!("ids: {} {}, expected: 1 2", a.id(), b.id());
+    println!("a calls: {}, expected: 2", a.calls().len());
+    let pool = (3..6).map(&make_conn).collect::<Vec<_>>();
+    println!("pool: {:?}, expected: [3, 4, 5]", ids(&pool));
+    println!("log: {:?}, expected: [\"1: select\", \"2: insert\"]", log);
+
+    // without the option, a local named `factory` is an initial value like any other
+    let factory = 7;
+
+    #[anonymous_trait::anonymous_trait(let conn = factory)]
+    impl Connection for u32 {
+        fn query(&mut self, sql: &str) -> String {
+            format!("result of {} on {}", sql, self)
+        }
+
+        fn id(&self) -> u32 {
+            *self
+        }
+    }
+
+    println!("conn: {}, expected: 7", conn.id());
+}
+
+fn ids(connections: &[impl Connection]) -> Vec<u32> {
+    connections
+        .iter()
+        .map(|connection| connection.id())
+        .collect()
+}
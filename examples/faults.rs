@@ -0,0 +1,61 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, PartialEq)]
+enum Error {
+    Unavailable,
+}
+
+trait Storage {
+    fn read(&self, key: &str) -> Result<String, Error>;
+    fn write(&mut self, key: &str, value: String);
+    async fn sync(&self) -> Result<(), Error>;
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    #[anonymous_trait::anonymous_trait(
+        let mut storage = Vec::<(String, String)>::new(),
+        read: fail_on(2, Err(Error::Unavailable)),
+        write: panic_on(3),
+        sync: delay(Duration::from_millis(50))
+    )]
+    impl Storage for Vec<(String, String)> {
+        fn read(&self, key: &str) -> Result<String, Error> {
+            Ok(format!("value of {}", key))
+        }
+
+        fn write(&mut self, key: &str, value: String) {
+            self.push((key.to_string(), value));
+        }
+
+        async fn sync(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    let reads = (0..3).map(|_| storage.read("a")).collect::<Vec<_>>();
+    println!(
+        "reads: {:?}, expected: [Ok(\"value of a\"), Err(Unavailable), Ok(\"value of a\")]",
+        reads
+    );
+
+    storage.write("a", "1".to_string());
+    storage.write("b", "2".to_string());
+    std::panic::set_hook(Box::new(|_| {}));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        storage.write("c", "3".to_string());
+    }));
+    let _ = std::panic::take_hook();
+    println!(
+        "write: {}, expected: fault injected into `Storage::write` on call 3",
+        result.unwrap_err().downcast::<String>().unwrap()
+    );
+
+    let start = Instant::now();
+    let synced = storage.sync().await;
+    println!(
+        "sync: {:?} after at least 50ms: {}, expected: Ok(()) after at least 50ms: true",
+        synced,
+        start.elapsed() >= Duration::from_millis(50)
+    );
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+#[anonymous_trait::stubbable]
+trait Store<V> {
+    type Error: std::fmt::Debug;
+    const NAME: &'static str;
+
+    fn get(&self, key: &str) -> Option<V>;
+    fn set(&mut self, key: &str, value: V) -> Result<(), Self::Error>;
+    fn len(&self) -> usize;
+
+    fn describe(&self) -> String {
+        format!("{} entries", self.len())
+    }
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    entries: HashMap<String, String>,
+}
+
+impl Store<String> for MemoryStore {
+    type Error = std::convert::Infallible;
+    const NAME: &'static str = "memory";
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.entries.get(key).cloned()
+    }
+
+    fn set(&mut self, key: &str, value: String) -> Result<(), Self::Error> {
+        self.entries.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn describe(&self) -> String {
+        format!("memory store with {} entries", self.len())
+    }
+}
+
+fn main() {
+    let store = MemoryStore::default();
+
+    // only `get` is overridden, everything else is forwarded to `store`
+    #[anonymous_trait::anonymous_trait(let mut store_mock = store, wrap, from_trait)]
+    impl Store<String> for MemoryStore {
+        fn get(&self, key: &str) -> Option<String> {
+            self.get(key).map(|value| value.to_uppercase())
+        }
+    }
+
+    run(&mut store_mock);
+}
+
+fn run<S: Store<String>>(store: &mut S) {
+    store.set("name", "mock".to_string()).unwrap();
+    println!(
+        "{}: {:?}, expected: Some(\"MOCK\")",
+        S::NAME,
+        store.get("name")
+    );
+    println!("len: {}, expected: 1", store.len());
+    // the override of the wrapped value is forwarded instead of the default of the trait
+    println!(
+        "describe: {}, expected: memory store with 1 entries",
+        store.describe()
+    );
+    assert_eq!(store.describe(), "memory store with 1 entries");
+}
@@ -0,0 +1,47 @@
+use std::sync::Mutex;
+
+trait Cat {
+    async fn meow_async(&self, id: u32) -> String;
+}
+
+async fn chorus(cat: &impl Cat, log: &Mutex<Vec<String>>) {
+    let first = async {
+        let meow = cat.meow_async(1).await;
+        log.lock().unwrap().push(meow);
+    };
+    let second = async {
+        let meow = cat.meow_async(2).await;
+        log.lock().unwrap().push(meow);
+    };
+    tokio::join!(first, second);
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = (), meow_async: gated)]
+    impl Cat for () {
+        async fn meow_async(&self, id: u32) -> String {
+            format!("meow {}", id)
+        }
+    }
+
+    let log = Mutex::new(Vec::new());
+    let test = async {
+        let gate = cat_mock.meow_async_gate();
+        // both calls are held at the gate
+        gate.until_waiting(2).await;
+        println!("waiting: {}, expected: 2", gate.waiting());
+        gate.step();
+        gate.until_passed(1).await;
+        println!(
+            "log after a step: {:?}, expected: [\"meow 1\"]",
+            log.lock().unwrap()
+        );
+        gate.open();
+    };
+    tokio::join!(chorus(&cat_mock, &log), test);
+    println!(
+        "log: {:?}, expected: [\"meow 1\", \"meow 2\"]",
+        log.lock().unwrap()
+    );
+}
@@ -0,0 +1,30 @@
+trait Cat {
+    fn meow(&self) -> String;
+    fn feed(&mut self, food: &str, grams: u32);
+}
+
+fn run(cat: &mut impl Cat) {
+    cat.feed("fish", 30);
+    cat.meow();
+    cat.feed("chicken", 20);
+}
+
+// Run with `cargo run --example golden --features serde`, and with `ANONYMOUS_TRAIT_BLESS=1` to
+// update the golden file.
+fn main() {
+    #[anonymous_trait::anonymous_trait(let mut cat_mock = Vec::<(String, u32)>::new(), spy(serde))]
+    impl Cat for Vec<(String, u32)> {
+        fn meow(&self) -> String {
+            format!("meow after eating {:?}", self)
+        }
+
+        fn feed(&mut self, food: &str, grams: u32) {
+            self.push((food.to_string(), grams));
+        }
+    }
+
+    run(&mut cat_mock);
+    println!("{}", cat_mock.calls_json());
+    cat_mock.assert_calls_golden("examples/golden/calls.json");
+    println!("the calls match examples/golden/calls.json");
+}
@@ -0,0 +1,56 @@
+trait Cat {
+    fn set_name(&mut self, name: String);
+    fn feed(&mut self, food: &str, grams: u32);
+}
+
+fn run(cat: &mut impl Cat) {
+    cat.set_name("hi".to_string());
+    cat.feed("fish", 30);
+    cat.feed("fish", 50);
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(
+        let mut cat_mock = String::new(),
+        expect set_name(eq("hi")) once,
+        expect feed(eq("fish"), pred(|grams| *grams <= 50)) times(2)
+    )]
+    impl Cat for String {
+        fn set_name(&mut self, name: String) {
+            *self = name;
+        }
+
+        fn feed(&mut self, food: &str, grams: u32) {
+            self.push_str(&format!(" ate {}g of {}", grams, food));
+        }
+    }
+
+    run(&mut cat_mock);
+    // the expected number of calls is verified here
+    drop(cat_mock);
+    println!("all calls matched the expectations");
+
+    #[anonymous_trait::anonymous_trait(
+        let mut cat_mock = String::new(),
+        expect feed(ne("chocolate"), _)
+    )]
+    impl Cat for String {
+        fn set_name(&mut self, name: String) {
+            *self = name;
+        }
+
+        fn feed(&mut self, food: &str, grams: u32) {
+            self.push_str(&format!(" ate {}g of {}", grams, food));
+        }
+    }
+
+    std::panic::set_hook(Box::new(|_| {}));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        cat_mock.feed("chocolate", 10);
+    }));
+    let _ = std::panic::take_hook();
+    println!(
+        "mismatch:\n{}\nexpected:\n`Cat::feed` was called with unexpected arguments\n- food: ne(\"chocolate\")\n+ food: \"chocolate\"\n  grams: 10\n",
+        result.unwrap_err().downcast::<String>().unwrap()
+    );
+}
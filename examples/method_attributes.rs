@@ -0,0 +1,43 @@
+trait Cat {
+    fn meow(&self, volume: u8) -> String;
+    #[cfg(debug_assertions)]
+    fn debug_name(&self) -> String;
+    #[cfg(not(debug_assertions))]
+    fn release_name(&self) -> String;
+}
+
+fn main() {
+    let name = "mock";
+
+    #[anonymous_trait::anonymous_trait(let cat_mock = (), spy)]
+    impl Cat for () {
+        #[inline]
+        #[allow(unused_variables)]
+        fn meow(&self, volume: u8) -> String {
+            "meow".to_string()
+        }
+
+        #[cfg(debug_assertions)]
+        fn debug_name(&self) -> String {
+            format!("{}-debug", name)
+        }
+
+        #[cfg(not(debug_assertions))]
+        fn release_name(&self) -> String {
+            format!("{}-release", name)
+        }
+    }
+
+    println!("meow: {}, expected: meow", cat_mock.meow(3));
+    #[cfg(debug_assertions)]
+    println!(
+        "debug_name: {}, expected: mock-debug",
+        cat_mock.debug_name()
+    );
+    #[cfg(not(debug_assertions))]
+    println!(
+        "release_name: {}, expected: mock-release",
+        cat_mock.release_name()
+    );
+    println!("calls: {:?}", cat_mock.calls());
+}
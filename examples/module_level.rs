@@ -0,0 +1,83 @@
+trait Cat {
+    fn meow(&self) -> String;
+    fn set_name(&mut self, new: String);
+    fn meow_count(&self) -> usize;
+}
+
+trait Greeter {
+    fn greet(&self, name: &str) -> String;
+    fn farewell(&self, name: &str) -> String;
+}
+
+#[anonymous_trait::stubbable]
+trait Clock {
+    fn now(&self) -> u64;
+    fn timezone(&self) -> String;
+}
+
+#[anonymous_trait::anonymous_trait(pub struct FixedClock { now: u64 } = (), stub(default))]
+impl Clock for () {
+    fn now(&self) -> u64 {
+        *now
+    }
+}
+
+struct RealGreeter;
+
+impl Greeter for RealGreeter {
+    fn greet(&self, name: &str) -> String {
+        format!("hello, {}", name)
+    }
+
+    fn farewell(&self, name: &str) -> String {
+        format!("bye, {}", name)
+    }
+}
+
+// A reusable stub: `suffix` is supplied to `CatStub::new` and the state starts as "tama".
+#[anonymous_trait::anonymous_trait(pub struct CatStub { suffix: String } = String::from("tama"))]
+impl Cat for String {
+    fn meow(&self) -> String {
+        format!("{}{}", self, suffix)
+    }
+
+    fn set_name(&mut self, new: String) {
+        *self = new;
+    }
+
+    fn meow_count(&self) -> usize {
+        0
+    }
+}
+
+#[anonymous_trait::anonymous_trait(struct LoudGreeter<'a> { volume: &'a str } = RealGreeter, wrap)]
+impl Greeter for RealGreeter {
+    fn greet(&self, name: &str) -> String {
+        format!("{} ({})", self.greet(name), volume)
+    }
+
+    fn farewell(&self, name: &str) -> String;
+}
+
+fn main() {
+    let mut cat = CatStub::new("!".to_string());
+    println!("meow: {}, expected: tama!", cat.meow());
+    cat.set_name("hi".to_string());
+    println!("meow: {}, expected: hi!", cat.meow());
+    println!("meow_count: {}, expected: 0", cat.meow_count());
+
+    let clock = FixedClock::new(42);
+    println!(
+        "now: {}, timezone: {:?}, expected: 42, \"\"",
+        clock.now(),
+        clock.timezone()
+    );
+
+    let volume = String::from("loud");
+    let greeter = LoudGreeter::new(&volume);
+    println!(
+        "greet: {}, expected: hello, cat (loud)",
+        greeter.greet("cat")
+    );
+    println!("farewell: {}, expected: bye, cat", greeter.farewell("cat"));
+}
@@ -0,0 +1,31 @@
+trait Cat {
+    fn meow(&self) -> String;
+    fn set_name(&mut self, new: String);
+}
+
+fn main() {
+    let suffix = "!";
+
+    #[anonymous_trait::anonymous_trait(let mut cat_mock = String::from("tama"), as CatMock, spy)]
+    impl Cat for String {
+        fn meow(&self) -> String {
+            format!("{}{}", self, suffix)
+        }
+
+        fn set_name(&mut self, new: String) {
+            *self = new;
+        }
+    }
+
+    fn rename(cat: &mut CatMock<'_>, name: &str) -> Vec<CatMockCall> {
+        cat.set_name(name.to_string());
+        cat.calls()
+    }
+
+    println!("meow: {}, expected: tama!", cat_mock.meow());
+    println!(
+        "calls: {:?}, expected: meow and set_name",
+        rename(&mut cat_mock, "hi")
+    );
+    println!("meow: {}, expected: hi!", cat_mock.meow());
+}
@@ -0,0 +1,69 @@
+#[derive(Debug, Clone, PartialEq)]
+enum Error {
+    Timeout,
+}
+
+#[anonymous_trait::stubbable]
+trait Client {
+    fn connect(&mut self) -> Result<u32, Error>;
+    fn status(&self) -> String;
+    fn name(&self) -> String;
+}
+
+fn connect_with_retry(client: &mut impl Client, attempts: usize) -> Vec<Result<u32, Error>> {
+    (0..attempts).map(|_| client.connect()).collect()
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(
+        let mut client = (),
+        connect: returns [Err(Error::Timeout), Err(Error::Timeout), Ok(1)],
+        status: returns ["connecting".to_string(), "ready".to_string()] repeat_last
+    )]
+    impl Client for () {
+        fn connect(&mut self) -> Result<u32, Error>;
+        fn status(&self) -> String;
+
+        fn name(&self) -> String {
+            "scripted".to_string()
+        }
+    }
+
+    println!(
+        "connect: {:?}, expected: [Err(Timeout), Err(Timeout), Ok(1)]",
+        connect_with_retry(&mut client, 3)
+    );
+    let statuses = (0..3).map(|_| client.status()).collect::<Vec<_>>();
+    println!(
+        "status: {:?}, expected: [\"connecting\", \"ready\", \"ready\"]",
+        statuses
+    );
+    println!("name: {}, expected: scripted", client.name());
+    std::panic::set_hook(Box::new(|_| {}));
+    let exhausted = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| client.connect()));
+    let _ = std::panic::take_hook();
+    println!(
+        "exhausted: {}, expected: `Client::connect` was called more times than its 3 scripted return value(s)",
+        exhausted.unwrap_err().downcast::<String>().unwrap()
+    );
+
+    // With `from_trait`, the scripted methods can be omitted.
+    #[anonymous_trait::anonymous_trait(
+        let mut client = (),
+        from_trait,
+        connect: returns [Ok(2)] repeat_last,
+        status: returns ["ready".to_string()],
+        connect: times(2)
+    )]
+    impl Client for () {
+        fn name(&self) -> String {
+            "omitted".to_string()
+        }
+    }
+
+    println!(
+        "connect: {:?}, expected: [Ok(2), Ok(2)]",
+        connect_with_retry(&mut client, 2)
+    );
+    println!("status: {}, expected: ready", client.status());
+}
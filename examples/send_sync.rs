@@ -0,0 +1,42 @@
+use std::{cell::Cell, rc::Rc};
+
+trait Service: Send + Sync {
+    fn handle(&self, request: u32) -> u32;
+}
+
+trait Local {
+    async fn fetch(&self) -> u32;
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let offset = 10;
+
+    // `Service: Send + Sync` requires the closures and the state to be `Send + Sync`
+    #[anonymous_trait::anonymous_trait(let service = 0u8, send, sync)]
+    impl Service for u8 {
+        fn handle(&self, request: u32) -> u32 {
+            request + offset
+        }
+    }
+
+    std::thread::scope(|scope| {
+        for request in 0..3 {
+            let service = &service;
+            scope.spawn(move || println!("handled: {}", service.handle(request)));
+        }
+    });
+
+    // `?Send` allows capturing `Rc` in async methods
+    let fetched = Rc::new(Cell::new(0));
+
+    #[anonymous_trait::anonymous_trait(let local = 0u8, ?Send)]
+    impl Local for u8 {
+        async fn fetch(&self) -> u32 {
+            fetched.set(fetched.get() + 1);
+            fetched.get()
+        }
+    }
+
+    println!("fetch: {}, expected: 1", local.fetch().await);
+}
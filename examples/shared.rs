@@ -0,0 +1,55 @@
+trait Counter {
+    fn increment(&mut self);
+    fn get(&self) -> u32;
+}
+
+struct Worker<C> {
+    counter: C,
+}
+
+impl<C: Counter> Worker<C> {
+    fn run(&mut self, times: u32) {
+        for _ in 0..times {
+            self.counter.increment();
+        }
+    }
+}
+
+fn main() {
+    let step = 2;
+
+    #[anonymous_trait::anonymous_trait(let counter = 0u32, shared(count))]
+    impl Counter for u32 {
+        fn increment(&mut self) {
+            *self += step;
+        }
+
+        fn get(&self) -> u32 {
+            *self
+        }
+    }
+
+    let mut worker = Worker { counter };
+    worker.run(3);
+    // The worker still owns the implementor, but the state can be read through the handle.
+    println!("count: {}, expected: 6", count.borrow());
+    *count.borrow_mut() = 100;
+    println!("get: {}, expected: 100", worker.counter.get());
+
+    #[anonymous_trait::anonymous_trait(let counter = 0u32, shared(total), send, sync)]
+    impl Counter for u32 {
+        fn increment(&mut self) {
+            *self += step;
+        }
+
+        fn get(&self) -> u32 {
+            *self
+        }
+    }
+
+    let mut worker = Worker { counter };
+    std::thread::scope(|scope| {
+        scope.spawn(|| worker.run(5));
+    });
+    println!("total: {}, expected: 10", total.lock().unwrap());
+}
@@ -0,0 +1,51 @@
+trait Cat {
+    fn meow(&self) -> String;
+    fn set_name(&mut self, new: String);
+    fn groom(&self, brush: Brush);
+}
+
+// does not implement Debug, so it is recorded as `_`
+struct Brush;
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let mut cat_mock = String::from("default"), spy)]
+    impl Cat for String {
+        fn meow(&self) -> String {
+            self.clone()
+        }
+
+        fn set_name(&mut self, new: String) {
+            if new.is_empty() {
+                panic!("empty name");
+            }
+            *self = new;
+        }
+
+        fn groom(&self, _brush: Brush) {}
+    }
+
+    run(&mut cat_mock);
+
+    std::panic::set_hook(Box::new(|_| {}));
+    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        cat_mock.set_name(String::new());
+    }));
+    let _ = std::panic::take_hook();
+
+    for call in cat_mock.calls() {
+        let outcome = if call.panicked {
+            "panicked".to_string()
+        } else {
+            call.ret.unwrap_or_default()
+        };
+        println!("{}({}) -> {}", call.method, call.args.join(", "), outcome);
+    }
+    assert_eq!(cat_mock.calls().len(), 5);
+}
+
+fn run(cat: &mut impl Cat) {
+    cat.meow();
+    cat.set_name("hi".to_string());
+    cat.meow();
+    cat.groom(Brush);
+}
@@ -0,0 +1,36 @@
+#[anonymous_trait::stubbable]
+trait Cat {
+    fn meow(&self) -> String;
+    fn set_name(&mut self, new: String);
+    fn age(&self) -> u8;
+    fn purr(&self) -> String {
+        "purr".to_string()
+    }
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = String::from("default"), stub)]
+    impl Cat for String {
+        fn meow(&self) -> String {
+            self.clone()
+        }
+    }
+
+    #[anonymous_trait::anonymous_trait(let mut default_mock = String::from("default"), stub(default))]
+    impl Cat for String {
+        fn meow(&self) -> String {
+            self.clone()
+        }
+    }
+
+    run(&mut default_mock);
+    println!("meow: {}, expected: default", cat_mock.meow());
+    // `cat_mock.age()` panics with "Cat::age not provided by anonymous impl at examples/stub.rs:12"
+}
+
+fn run(cat: &mut impl Cat) {
+    cat.set_name("hi".to_string());
+    println!("meow: {}, expected: default", cat.meow());
+    println!("purr: {}, expected: purr", cat.purr());
+    println!("age: {}, expected: 0", cat.age());
+}
@@ -0,0 +1,38 @@
+trait Backend {
+    fn fetch(&self, key: &str) -> Result<String, String>;
+}
+
+fn fetch_all(backend: &impl Backend, keys: &[&str]) -> Vec<Result<String, String>> {
+    keys.iter().map(|key| backend.fetch(key)).collect()
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let backend = (), swappable)]
+    impl Backend for () {
+        fn fetch(&self, key: &str) -> Result<String, String> {
+            Ok(format!("value of {}", key))
+        }
+    }
+
+    println!(
+        "healthy: {:?}, expected: [Ok(\"value of a\"), Ok(\"value of b\")]",
+        fetch_all(&backend, &["a", "b"])
+    );
+
+    // The backend goes down in the next phase of the test.
+    backend.swap_fetch(|_state, key| Err(format!("{} is unavailable", key)));
+    println!(
+        "outage: {:?}, expected: [Err(\"a is unavailable\")]",
+        fetch_all(&backend, &["a"])
+    );
+
+    let mut retries = 0;
+    backend.swap_fetch(move |_state, key| {
+        retries += 1;
+        Ok(format!("value of {} after {} retries", key, retries))
+    });
+    println!(
+        "recovered: {:?}, expected: [Ok(\"value of a after 1 retries\"), Ok(\"value of b after 2 retries\")]",
+        fetch_all(&backend, &["a", "b"])
+    );
+}
@@ -0,0 +1,41 @@
+trait Cat {
+    fn feed(&mut self, food: &str, grams: u32);
+    fn groom(&self, brush: Brush);
+    async fn meow_async(&self) -> String;
+}
+
+// not `Debug`, so it is traced as `_`
+struct Brush;
+
+async fn run(cat: &mut impl Cat) {
+    cat.feed("fish", 30);
+    cat.groom(Brush);
+    println!("{}", cat.meow_async().await);
+}
+
+// Run with `cargo run --example tracing --features tracing`.
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .init();
+
+    #[anonymous_trait::anonymous_trait(let mut cat_mock = 0u32, tracing)]
+    impl Cat for u32 {
+        fn feed(&mut self, food: &str, grams: u32) {
+            tracing::info!("eating {}", food);
+            *self += grams;
+        }
+
+        fn groom(&self, brush: Brush) {
+            let Brush = brush;
+        }
+
+        async fn meow_async(&self) -> String {
+            tracing::info!("meowing");
+            format!("meow after eating {}g", self)
+        }
+    }
+
+    run(&mut cat_mock).await;
+}
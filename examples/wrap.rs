@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+trait Db {
+    fn get(&self, key: &str) -> Option<String>;
+    fn set(&mut self, key: &str, value: String);
+    fn len(&self) -> usize;
+}
+
+#[derive(Default)]
+struct RealDb {
+    entries: HashMap<String, String>,
+}
+
+impl Db for RealDb {
+    fn get(&self, key: &str) -> Option<String> {
+        self.entries.get(key).cloned()
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        self.entries.insert(key.to_string(), value);
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+fn main() {
+    let real_db = RealDb::default();
+
+    #[anonymous_trait::anonymous_trait(let mut db = real_db, wrap)]
+    impl Db for RealDb {
+        fn get(&self, key: &str) -> Option<String> {
+            if key == "secret" {
+                return Some("overridden".to_string());
+            }
+            // calls the wrapped implementation
+            self.get(key)
+        }
+
+        fn set(&mut self, key: &str, value: String);
+        fn len(&self) -> usize;
+    }
+
+    run(&mut db);
+}
+
+fn run(db: &mut impl Db) {
+    db.set("name", "mock".to_string());
+    println!("name: {:?}, expected: Some(\"mock\")", db.get("name"));
+    println!(
+        "secret: {:?}, expected: Some(\"overridden\")",
+        db.get("secret")
+    );
+    println!("len: {}, expected: 1", db.len());
+}
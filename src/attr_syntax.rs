@@ -1,19 +1,293 @@
 use quote::ToTokens as _;
 use syn::{parse::Parse, Token};
 
+use crate::impl_syntax::AnonymousImpl;
+
 #[allow(dead_code)]
 pub(crate) struct LetDefault {
     pub let_token: Token![let],
     pub pat_ident: syn::PatIdent,
     pub eq_token: Token![=],
     pub expr: syn::Expr,
+    pub options: Options,
+    /// `struct Name { captures } = expr` instead of the `let` binding, which defines the
+    /// implementor at module level. `pat_ident` is then the name of the struct.
+    pub module: Option<ModuleStruct>,
+}
+
+/// A struct holding the state and the captured values, constructed by `Name::new(captures)`.
+pub(crate) struct ModuleStruct {
+    pub vis: syn::Visibility,
+    pub struct_token: Token![struct],
+    pub ident: syn::Ident,
+    pub generics: syn::Generics,
+    pub fields: syn::FieldsNamed,
+}
+
+/// Options following the `let` binding, separated by commas.
+#[derive(Default)]
+pub(crate) struct Options {
+    /// `wrap`: forward the methods without a body to the initial value.
+    pub wrap: bool,
+    /// `factory`, with `_` as the initial value: bind a closure creating an implementor from its
+    /// initial state, of the type after `for`, sharing the methods with the other implementors.
+    pub factory: bool,
+    /// `spy`: record every call of the generated methods.
+    pub spy: bool,
+    /// `spy(serde)`: also serialize the recorded calls and compare them with a golden file, with the
+    /// `serde` feature.
+    pub serde: bool,
+    /// `stub` or `stub(default)`: generate the required methods missing from the impl, using the
+    /// trait definition exported by `#[anonymous_trait::stubbable]`.
+    pub stub: Option<Stub>,
+    /// `from_trait`: consult the trait definition exported by `#[anonymous_trait::stubbable]`.
+    pub from_trait: bool,
+    /// `lock_free`: store `Fn` closures for `&self` methods and call them without locking.
+    /// Async `&self` methods with `async_closures` are always lock-free.
+    pub lock_free: bool,
+    /// `async_closures`: store `async` closures bounded by `AsyncFnMut` for async methods, so that
+    /// their bodies can await.
+    pub async_closures: bool,
+    /// `send` or `?Send`: whether every closure must be `Send`. By default, only the closures of
+    /// async methods must be.
+    pub send: Option<bool>,
+    /// `sync`: every closure must be `Send + Sync`, so that the generated struct can be `Sync`.
+    pub sync: bool,
+    /// `swappable`: store boxed closures, which can be replaced with the generated `swap_{method}`
+    /// setters.
+    pub swappable: bool,
+    /// `shared(handle)`: keep the state in `Rc<RefCell<_>>`, or `Arc<Mutex<_>>` with `send` or
+    /// `sync`, and bind a clone of it to `handle` to inspect the state while the implementor is
+    /// alive.
+    pub shared: Option<syn::Ident>,
+    /// `tracing`: emit a `tracing` span and event for each call, with the `tracing` feature.
+    pub tracing: bool,
+    /// `as Name`: name the generated struct `Name<'_>`, whose closures are then type-erased, so that
+    /// it can be written in the signatures of helper functions.
+    pub name: Option<syn::Ident>,
+    /// `method: option` entries configuring a single method.
+    pub methods: Vec<MethodOption>,
+}
+
+/// The body of the generated methods in `stub` mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Stub {
+    /// `stub`: panics with `unimplemented!`.
+    Unimplemented,
+    /// `stub(default)`: returns `Default::default()`.
+    Default,
+}
+
+impl Options {
+    /// Whether the method is dispatched to a `Fn` closure without locking. An `async` closure of a
+    /// `&self` method is never locked, because the guard would be held across the await and block
+    /// a concurrent call forever.
+    pub(crate) fn is_lock_free(&self, sig: &syn::Signature) -> bool {
+        (self.lock_free || self.is_async_closure(sig))
+            && sig.receiver().is_some_and(|receiver| {
+                receiver.reference.is_some() && receiver.mutability.is_none()
+            })
+    }
+    /// Whether the method is dispatched to an `async` closure.
+    pub(crate) fn is_async_closure(&self, sig: &syn::Signature) -> bool {
+        self.async_closures && sig.asyncness.is_some()
+    }
+    /// Whether the closure of the method must be `Send`.
+    pub(crate) fn requires_send(&self, sig: &syn::Signature) -> bool {
+        self.sync || self.send.unwrap_or(sig.asyncness.is_some())
+    }
+    /// Whether the shared state is kept in `Arc<Mutex<_>>` instead of `Rc<RefCell<_>>`.
+    pub(crate) fn shares_across_threads(&self) -> bool {
+        self.send == Some(true) || self.sync
+    }
+    /// Whether the trait definition exported by `#[anonymous_trait::stubbable]` is needed.
+    pub(crate) fn consults_trait(&self) -> bool {
+        self.from_trait || self.stub.is_some()
+    }
+    /// Whether the generated struct counts the calls of each method.
+    pub(crate) fn counts_calls(&self) -> bool {
+        self.spy
+            || self.methods.iter().any(|option| {
+                matches!(
+                    option.kind,
+                    MethodOptionKind::Times(_)
+                        | MethodOptionKind::Fault(Fault::PanicOn(_) | Fault::FailOn(..))
+                )
+            })
+    }
+    /// The expected number of calls of each method.
+    pub(crate) fn expectations(&self) -> impl Iterator<Item = (&syn::Ident, &Times)> {
+        self.methods.iter().filter_map(|option| match &option.kind {
+            MethodOptionKind::Times(times) => Some((&option.method, times)),
+            _ => None,
+        })
+    }
+    /// The faults injected into the method.
+    pub(crate) fn faults<'a>(&'a self, method: &'a syn::Ident) -> impl Iterator<Item = &'a Fault> {
+        self.methods
+            .iter()
+            .filter_map(move |option| match &option.kind {
+                MethodOptionKind::Fault(fault) if &option.method == method => Some(fault),
+                _ => None,
+            })
+    }
+    /// The matchers the arguments of each method must satisfy.
+    pub(crate) fn matchers(&self) -> impl Iterator<Item = (&syn::Ident, &Matchers)> {
+        self.methods.iter().filter_map(|option| match &option.kind {
+            MethodOptionKind::Args(matchers) => Some((&option.method, matchers)),
+            _ => None,
+        })
+    }
+    /// The scripted return values of the method.
+    pub(crate) fn returns(&self, method: &syn::Ident) -> Option<&Returns> {
+        self.methods.iter().find_map(|option| match &option.kind {
+            MethodOptionKind::Returns(returns) if &option.method == method => Some(returns),
+            _ => None,
+        })
+    }
+    /// Checks that every `method: option` entry names a method of the impl that it applies to, and
+    /// that it is not given twice, except for the faults.
+    pub(crate) fn validate_methods(&self, input: &AnonymousImpl) -> syn::Result<()> {
+        let mut errors = Vec::new();
+        for (index, option) in self.methods.iter().enumerate() {
+            let method = &option.method;
+            let Some(sig) = input.signatures().find(|sig| &sig.ident == method) else {
+                errors.push(syn::Error::new(
+                    method.span(),
+                    format!("no method `{}` in this anonymous impl", method),
+                ));
+                continue;
+            };
+            let duplicate = self.methods[..index].iter().any(|other| {
+                &other.method == method
+                    && !matches!(option.kind, MethodOptionKind::Fault(_))
+                    && std::mem::discriminant(&other.kind) == std::mem::discriminant(&option.kind)
+            });
+            let message = match &option.kind {
+                _ if duplicate => format!("duplicate {} for `{}`", option.kind.name(), method),
+                MethodOptionKind::Times(_)
+                | MethodOptionKind::Fault(Fault::PanicOn(_) | Fault::FailOn(..))
+                    if sig.receiver().is_none() =>
+                {
+                    format!(
+                        "cannot count calls of `{}` because it has no `self` receiver",
+                        method
+                    )
+                }
+                MethodOptionKind::Args(matchers)
+                    if crate::impl_trait::arg_pats(sig).len() != matchers.len() =>
+                {
+                    format!(
+                        "`{}` takes {} argument(s), but {} matcher(s) are given",
+                        method,
+                        crate::impl_trait::arg_pats(sig).len(),
+                        matchers.len()
+                    )
+                }
+                MethodOptionKind::Gated if !crate::gate::is_gatable(sig) => format!(
+                    "`{}` cannot be gated because it is not an async method taking `self`",
+                    method
+                ),
+                _ => continue,
+            };
+            errors.push(syn::Error::new(method.span(), message));
+        }
+        match errors.into_iter().reduce(|mut errors, error| {
+            errors.combine(error);
+            errors
+        }) {
+            Some(errors) => Err(errors),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `method: option`
+pub(crate) struct MethodOption {
+    pub method: syn::Ident,
+    pub kind: MethodOptionKind,
+}
+
+pub(crate) enum MethodOptionKind {
+    /// Expected number of calls, checked when the implementor is dropped.
+    Times(Times),
+    /// Values returned by the successive calls.
+    Returns(Returns),
+    /// Matchers checked against the arguments of every call, given by
+    /// `expect method(matchers) times`.
+    Args(Matchers),
+    /// A fault injected into the method.
+    Fault(Fault),
+    /// `gated`: the calls wait at a gate opened by the test.
+    Gated,
+}
+
+impl MethodOptionKind {
+    /// The name of the option in error messages.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Times(_) => "expected number of calls",
+            Self::Returns(_) => "`returns`",
+            Self::Args(_) => "`expect`",
+            Self::Fault(_) => "fault",
+            Self::Gated => "`gated`",
+        }
+    }
+}
+
+pub(crate) enum Fault {
+    /// `panic_on(n)`: panics on the `n`th call.
+    PanicOn(syn::Expr),
+    /// `fail_on(n, value)`: returns the value on the `n`th call instead of running the method.
+    FailOn(syn::Expr, syn::Expr),
+    /// `delay(duration)`: sleeps for the `std::time::Duration` before every call, without blocking
+    /// the executor in async methods.
+    Delay(syn::Expr),
+}
+
+pub(crate) type Matchers = syn::punctuated::Punctuated<Matcher, Token![,]>;
+
+/// A matcher of a single argument.
+pub(crate) enum Matcher {
+    /// `eq(value)`
+    Eq(syn::Expr),
+    /// `ne(value)`
+    Ne(syn::Expr),
+    /// `pred(|arg| condition)`, called with a reference to the argument.
+    Pred(syn::Expr),
+    /// `_`
+    Any,
+}
+
+/// `returns [values]` or `returns [values] repeat_last`
+pub(crate) struct Returns {
+    pub values: syn::punctuated::Punctuated<syn::Expr, Token![,]>,
+    /// Whether the last value is returned again once the others are exhausted, instead of
+    /// panicking.
+    pub repeat_last: bool,
+}
+
+pub(crate) enum Times {
+    /// `times(n)`
+    Exactly(syn::Expr),
+    /// `at_least(n)`
+    AtLeast(syn::Expr),
+    /// `at_most(n)`
+    AtMost(syn::Expr),
+    /// `once`
+    Once,
+    /// `never`
+    Never,
 }
 
 impl Parse for LetDefault {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let expr: syn::ExprLet = input.parse()?;
-        let syn::Pat::Ident(pat_ident) = *expr.pat else {
-            let span = expr
+        if input.peek(Token![struct]) || input.peek(Token![pub]) {
+            return parse_module(input);
+        }
+        let expr_let: syn::ExprLet = input.parse()?;
+        let syn::Pat::Ident(pat_ident) = *expr_let.pat else {
+            let span = expr_let
                 .pat
                 .to_token_stream()
                 .into_iter()
@@ -22,11 +296,717 @@ impl Parse for LetDefault {
                 .span();
             return Err(syn::Error::new(span, "expected identifier"));
         };
+        let expr = *expr_let.expr;
+        let options: Options = input.parse()?;
+        if options.factory != matches!(expr, syn::Expr::Infer(_)) {
+            let message = if options.factory {
+                "the initial value of a factory is given to the closure, so it must be `_`"
+            } else {
+                "`_` as the initial value requires the `factory` option"
+            };
+            return Err(syn::Error::new_spanned(&expr, message));
+        }
+        if options.swappable && (options.factory || options.lock_free) {
+            return Err(syn::Error::new(
+                pat_ident.ident.span(),
+                "`swappable` cannot be used with `factory` or `lock_free`",
+            ));
+        }
+        if options.async_closures && options.shared.is_some() {
+            return Err(syn::Error::new(
+                pat_ident.ident.span(),
+                "`async_closures` cannot be used with `shared`, because the state would stay \
+                 borrowed across the await",
+            ));
+        }
+        if options.factory && options.shared.is_some() {
+            return Err(syn::Error::new(
+                pat_ident.ident.span(),
+                "`shared` cannot be used with `factory`, whose implementors own their state",
+            ));
+        }
         Ok(Self {
-            let_token: expr.let_token,
+            let_token: expr_let.let_token,
             pat_ident,
-            eq_token: expr.eq_token,
-            expr: *expr.expr,
+            eq_token: expr_let.eq_token,
+            expr,
+            options,
+            module: None,
+        })
+    }
+}
+
+fn parse_module(input: syn::parse::ParseStream) -> syn::Result<LetDefault> {
+    let vis = input.parse()?;
+    let struct_token = input.parse()?;
+    let ident: syn::Ident = input.parse()?;
+    let mut generics: syn::Generics = input.parse()?;
+    generics.where_clause = input.parse()?;
+    let fields = input.parse()?;
+    let eq_token = input.parse()?;
+    let expr = input.parse()?;
+    let options: Options = input.parse()?;
+    if options.spy
+        || !options.methods.is_empty()
+        || options.lock_free
+        || options.async_closures
+        || options.send.is_some()
+        || options.sync
+        || options.name.is_some()
+        || options.shared.is_some()
+        || options.swappable
+        || options.tracing
+        || options.factory
+    {
+        return Err(syn::Error::new(
+            ident.span(),
+            "`spy`, method options, `lock_free`, `async_closures`, `send`, `sync`, `shared`, \
+             `swappable`, `tracing`, `factory` and `as` are not supported for a module-level \
+             struct",
+        ));
+    }
+    Ok(LetDefault {
+        let_token: Default::default(),
+        pat_ident: syn::PatIdent {
+            attrs: vec![],
+            by_ref: None,
+            mutability: None,
+            ident: ident.clone(),
+            subpat: None,
+        },
+        eq_token,
+        expr,
+        options,
+        module: Some(ModuleStruct {
+            vis,
+            struct_token,
+            ident,
+            generics,
+            fields,
+        }),
+    })
+}
+
+impl Parse for Options {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut options = Self::default();
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            if input.peek(Token![?]) {
+                let question: Token![?] = input.parse()?;
+                let ident: syn::Ident = input.parse()?;
+                if ident != "Send" {
+                    return Err(syn::Error::new(ident.span(), "expected `?Send`"));
+                }
+                if options.send == Some(true) || options.sync {
+                    return Err(syn::Error::new(
+                        question.span,
+                        "`?Send` conflicts with `send` and `sync`",
+                    ));
+                }
+                options.send = Some(false);
+                continue;
+            }
+            if input.peek(Token![as]) {
+                let as_token: Token![as] = input.parse()?;
+                if options.name.is_some() {
+                    return Err(syn::Error::new(as_token.span, "duplicate `as` option"));
+                }
+                options.name = Some(input.parse()?);
+                continue;
+            }
+            let ident: syn::Ident = input.parse()?;
+            if input.peek(Token![:]) {
+                input.parse::<Token![:]>()?;
+                options.methods.push(MethodOption {
+                    method: ident,
+                    kind: input.parse()?,
+                });
+                continue;
+            }
+            match ident.to_string().as_str() {
+                "expect" => {
+                    let method: syn::Ident = input.parse()?;
+                    let content;
+                    syn::parenthesized!(content in input);
+                    let matchers = content.parse_terminated(Matcher::parse, Token![,])?;
+                    if input.peek(syn::Ident) {
+                        options.methods.push(MethodOption {
+                            method: method.clone(),
+                            kind: MethodOptionKind::Times(input.parse()?),
+                        });
+                    }
+                    options.methods.push(MethodOption {
+                        method,
+                        kind: MethodOptionKind::Args(matchers),
+                    });
+                }
+                "spy" if input.peek(syn::token::Paren) => {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    let kind: syn::Ident = content.parse()?;
+                    if kind != "serde" || !content.is_empty() {
+                        return Err(syn::Error::new(kind.span(), "expected `serde`"));
+                    }
+                    if !cfg!(feature = "serde") {
+                        return Err(syn::Error::new(
+                            kind.span(),
+                            "`spy(serde)` requires the `serde` feature of `anonymous-trait`",
+                        ));
+                    }
+                    options.spy = true;
+                    options.serde = true;
+                }
+                "spy" => options.spy = true,
+                "wrap" => options.wrap = true,
+                "factory" => options.factory = true,
+                "from_trait" => options.from_trait = true,
+                "lock_free" => options.lock_free = true,
+                "swappable" => options.swappable = true,
+                "async_closures" => options.async_closures = true,
+                "tracing" if !cfg!(feature = "tracing") => {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "the `tracing` option requires the `tracing` feature of `anonymous-trait`",
+                    ));
+                }
+                "tracing" => options.tracing = true,
+                "send" | "sync" if options.send == Some(false) => {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "`?Send` conflicts with `send` and `sync`",
+                    ));
+                }
+                "send" => options.send = Some(true),
+                "sync" => options.sync = true,
+                "stub" if input.peek(syn::token::Paren) => {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    let kind: syn::Ident = content.parse()?;
+                    if kind != "default" || !content.is_empty() {
+                        return Err(syn::Error::new(kind.span(), "expected `default`"));
+                    }
+                    options.stub = Some(Stub::Default);
+                }
+                "stub" => options.stub = Some(Stub::Unimplemented),
+                "shared" => {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    options.shared = Some(content.parse()?);
+                    if !content.is_empty() {
+                        return Err(content.error("expected an identifier for the handle"));
+                    }
+                }
+                _ => return Err(syn::Error::new(ident.span(), "unknown option")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+impl Parse for MethodOptionKind {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let ident: syn::Ident = input.parse()?;
+        if ident == "returns" {
+            let content;
+            syn::bracketed!(content in input);
+            let values = content.parse_terminated(syn::Expr::parse, Token![,])?;
+            let repeat_last = match input.parse::<Option<syn::Ident>>()? {
+                Some(ident) if ident == "repeat_last" => true,
+                Some(ident) => return Err(syn::Error::new(ident.span(), "expected `repeat_last`")),
+                None => false,
+            };
+            return Ok(Self::Returns(Returns {
+                values,
+                repeat_last,
+            }));
+        }
+        match ident.to_string().as_str() {
+            "gated" => return Ok(Self::Gated),
+            "panic_on" => return Ok(Self::Fault(Fault::PanicOn(parenthesized_expr(input)?))),
+            "delay" => return Ok(Self::Fault(Fault::Delay(parenthesized_expr(input)?))),
+            "fail_on" => {
+                let content;
+                syn::parenthesized!(content in input);
+                let n = content.parse()?;
+                content.parse::<Token![,]>()?;
+                let value = content.parse()?;
+                content.parse::<Option<Token![,]>>()?;
+                if !content.is_empty() {
+                    return Err(content.error("expected `fail_on(n, value)`"));
+                }
+                return Ok(Self::Fault(Fault::FailOn(n, value)));
+            }
+            _ => {}
+        }
+        Ok(Self::Times(Times::parse_after(ident, input)?))
+    }
+}
+
+impl Parse for Times {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let ident = input.parse()?;
+        Self::parse_after(ident, input)
+    }
+}
+
+impl Times {
+    fn parse_after(ident: syn::Ident, input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(match ident.to_string().as_str() {
+            "once" => Times::Once,
+            "never" => Times::Never,
+            "times" => Times::Exactly(parenthesized_expr(input)?),
+            "at_least" => Times::AtLeast(parenthesized_expr(input)?),
+            "at_most" => Times::AtMost(parenthesized_expr(input)?),
+            _ => return Err(syn::Error::new(ident.span(), "unknown method option")),
         })
     }
 }
+
+impl Parse for Matcher {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if input.peek(Token![_]) {
+            input.parse::<Token![_]>()?;
+            return Ok(Self::Any);
+        }
+        let ident: syn::Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "eq" => Ok(Self::Eq(parenthesized_expr(input)?)),
+            "ne" => Ok(Self::Ne(parenthesized_expr(input)?)),
+            "pred" => Ok(Self::Pred(parenthesized_expr(input)?)),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "expected `eq(..)`, `ne(..)`, `pred(..)` or `_`",
+            )),
+        }
+    }
+}
+
+fn parenthesized_expr(input: syn::parse::ParseStream) -> syn::Result<syn::Expr> {
+    let content;
+    let paren = syn::parenthesized!(content in input);
+    let expr: syn::Expr = content.parse()?;
+    if !content.is_empty() {
+        return Err(syn::Error::new(
+            paren.span.join(),
+            "expected a single expression",
+        ));
+    }
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn no_options() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat::default()
+        };
+        assert!(!attr.options.wrap);
+        assert!(!attr.options.spy);
+    }
+
+    #[test]
+    fn spy() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat::default(), spy
+        };
+        assert_eq!(attr.expr.to_token_stream().to_string(), "Cat :: default ()");
+        assert!(attr.options.spy);
+    }
+
+    #[test]
+    fn wrap() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = RealDb::new(), wrap, spy
+        };
+        assert!(attr.options.wrap);
+        assert_eq!(attr.expr.to_token_stream().to_string(), "RealDb :: new ()");
+        assert!(attr.options.spy);
+
+        // a function named `wrap` is an initial value like any other
+        let attr: LetDefault = parse_quote! {
+            let my_mock = wrap(real_db)
+        };
+        assert!(!attr.options.wrap);
+        assert_eq!(attr.expr.to_token_stream().to_string(), "wrap (real_db)");
+    }
+
+    #[test]
+    fn factory() {
+        let attr: LetDefault = parse_quote! {
+            let make_cat = _, factory, spy
+        };
+        assert!(attr.options.factory);
+        assert!(!attr.options.wrap);
+        assert!(attr.options.spy);
+
+        // a local named `factory` is an initial value like any other
+        let attr: LetDefault = parse_quote! {
+            let my_mock = factory
+        };
+        assert!(!attr.options.factory);
+        assert_eq!(attr.expr.to_token_stream().to_string(), "factory");
+
+        let result = syn::parse_str::<LetDefault>("let make_cat = Cat::default(), factory");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "the initial value of a factory is given to the closure, so it must be `_`"
+        );
+        let result = syn::parse_str::<LetDefault>("let make_cat = _");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "`_` as the initial value requires the `factory` option"
+        );
+    }
+
+    #[test]
+    fn method_options() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, meow: times(2), set_name: never, spy
+        };
+        assert!(attr.options.spy);
+        let methods = attr
+            .options
+            .methods
+            .iter()
+            .map(|option| option.method.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(methods, vec!["meow", "set_name"]);
+        assert!(matches!(
+            attr.options.methods[0].kind,
+            MethodOptionKind::Times(Times::Exactly(_))
+        ));
+        assert!(matches!(
+            attr.options.methods[1].kind,
+            MethodOptionKind::Times(Times::Never)
+        ));
+    }
+
+    #[test]
+    fn returns() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, meow: returns [Ok(1), Err(Timeout)], age: returns [2] repeat_last, meow: once
+        };
+        let returns = attr.options.returns(&parse_quote!(meow)).unwrap();
+        assert_eq!(returns.values.len(), 2);
+        assert!(!returns.repeat_last);
+        assert!(
+            attr.options
+                .returns(&parse_quote!(age))
+                .unwrap()
+                .repeat_last
+        );
+        assert_eq!(attr.options.expectations().count(), 1);
+        let result = syn::parse_str::<LetDefault>("let my_mock = Cat, meow: returns [1] forever");
+        assert_eq!(result.err().unwrap().to_string(), "expected `repeat_last`");
+    }
+
+    #[test]
+    fn expect() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, expect set_name(eq("hi"), _) once, expect meow(pred(|v| *v > 3))
+        };
+        assert_eq!(attr.options.expectations().count(), 1);
+        let matchers = attr.options.matchers().collect::<Vec<_>>();
+        assert_eq!(matchers.len(), 2);
+        assert_eq!(matchers[0].0, "set_name");
+        assert!(matches!(matchers[0].1[0], Matcher::Eq(_)));
+        assert!(matches!(matchers[0].1[1], Matcher::Any));
+        assert!(matches!(matchers[1].1[0], Matcher::Pred(_)));
+        let result = syn::parse_str::<LetDefault>("let my_mock = Cat, expect meow(equals(1))");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "expected `eq(..)`, `ne(..)`, `pred(..)` or `_`"
+        );
+    }
+
+    #[test]
+    fn faults() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat,
+            meow: panic_on(3),
+            meow: fail_on(2, Err(Error::Timeout)),
+            meow: delay(std::time::Duration::from_millis(10))
+        };
+        assert!(attr.options.counts_calls());
+        let method = parse_quote!(meow);
+        let faults = attr.options.faults(&method).collect::<Vec<_>>();
+        assert!(matches!(faults[0], Fault::PanicOn(_)));
+        assert!(matches!(faults[1], Fault::FailOn(..)));
+        assert!(matches!(faults[2], Fault::Delay(_)));
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, meow: delay(std::time::Duration::from_millis(10))
+        };
+        assert!(!attr.options.counts_calls());
+    }
+
+    #[test]
+    fn gated() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, meow: gated
+        };
+        assert!(matches!(
+            attr.options.methods[0].kind,
+            MethodOptionKind::Gated
+        ));
+        assert!(!attr.options.counts_calls());
+    }
+
+    #[test]
+    fn unknown_method_option() {
+        let result = syn::parse_str::<LetDefault>("let my_mock = Cat, meow: sometimes");
+        assert_eq!(result.err().unwrap().to_string(), "unknown method option");
+    }
+
+    #[test]
+    fn stub() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, stub
+        };
+        assert_eq!(attr.options.stub, Some(Stub::Unimplemented));
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, stub(default)
+        };
+        assert_eq!(attr.options.stub, Some(Stub::Default));
+        assert!(attr.options.consults_trait());
+    }
+
+    #[test]
+    fn from_trait() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = real_cat, wrap, from_trait
+        };
+        assert!(attr.options.from_trait);
+        assert!(attr.options.consults_trait());
+    }
+
+    #[test]
+    fn lock_free() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, lock_free
+        };
+        assert!(attr.options.is_lock_free(&parse_quote!(fn meow(&self))));
+        assert!(!attr
+            .options
+            .is_lock_free(&parse_quote!(fn set_name(&mut self))));
+        assert!(!attr.options.is_lock_free(&parse_quote!(fn new())));
+    }
+
+    #[test]
+    fn async_closures() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, async_closures
+        };
+        assert!(attr
+            .options
+            .is_async_closure(&parse_quote!(async fn meow(&self))));
+        assert!(!attr.options.is_async_closure(&parse_quote!(fn meow(&self))));
+        assert!(attr
+            .options
+            .is_lock_free(&parse_quote!(async fn meow(&self))));
+        assert!(!attr
+            .options
+            .is_lock_free(&parse_quote!(async fn set_name(&mut self))));
+        assert!(!attr.options.is_lock_free(&parse_quote!(fn meow(&self))));
+        let result = syn::parse_str::<LetDefault>("let my_mock = Cat, async_closures, shared(cat)");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "`async_closures` cannot be used with `shared`, because the state would stay borrowed \
+             across the await"
+        );
+    }
+
+    #[test]
+    fn send_sync() {
+        let sync_fn = parse_quote!(fn meow(&self));
+        let async_fn = parse_quote!(async fn meow(&self));
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat
+        };
+        assert!(!attr.options.requires_send(&sync_fn));
+        assert!(attr.options.requires_send(&async_fn));
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, send
+        };
+        assert!(attr.options.requires_send(&sync_fn));
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, ?Send
+        };
+        assert!(!attr.options.requires_send(&async_fn));
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, sync
+        };
+        assert!(attr.options.sync);
+        assert!(attr.options.requires_send(&sync_fn));
+        let result = syn::parse_str::<LetDefault>("let my_mock = Cat, sync, ?Send");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "`?Send` conflicts with `send` and `sync`"
+        );
+    }
+
+    #[test]
+    fn name() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, as CatMock, spy
+        };
+        assert_eq!(attr.options.name.unwrap(), "CatMock");
+        assert!(attr.options.spy);
+    }
+
+    #[test]
+    fn module() {
+        let attr: LetDefault = parse_quote! {
+            pub struct CatStub<'a> { name: &'a str } = Cat::default(), wrap, stub
+        };
+        let module = attr.module.unwrap();
+        assert_eq!(module.ident, "CatStub");
+        assert_eq!(module.fields.named.len(), 1);
+        assert!(attr.options.wrap);
+        assert_eq!(attr.expr.to_token_stream().to_string(), "Cat :: default ()");
+        assert!(attr.options.stub.is_some());
+        let result = syn::parse_str::<LetDefault>("struct CatStub {} = Cat, spy");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn shared() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, shared(cat_state)
+        };
+        assert_eq!(attr.options.shared.as_ref().unwrap(), "cat_state");
+        assert!(!attr.options.shares_across_threads());
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, shared(cat_state), sync
+        };
+        assert!(attr.options.shares_across_threads());
+        let result = syn::parse_str::<LetDefault>("let make_cat = _, factory, shared(cat_state)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn swappable() {
+        let attr: LetDefault = parse_quote! {
+            let my_mock = Cat, swappable
+        };
+        assert!(attr.options.swappable);
+        let result = syn::parse_str::<LetDefault>("let my_mock = Cat, swappable, lock_free");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "`swappable` cannot be used with `factory` or `lock_free`"
+        );
+    }
+
+    #[test]
+    fn validate_methods() {
+        let input: AnonymousImpl = parse_quote! {
+            impl Something for Cat {
+                fn new() -> Self {
+                    Self::default()
+                }
+                fn feed(&mut self, food: &str, grams: u32) {}
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+                async fn purr(&self) {}
+            }
+        };
+        let error = |options: &str| {
+            let attr = syn::parse_str::<LetDefault>(&format!("let my_mock = Cat, {options}"));
+            let errors = attr.unwrap().options.validate_methods(&input).err();
+            errors.map(|errors| {
+                errors
+                    .into_iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(
+            error("meow: once, expect feed(eq(\"fish\"), _) once, purr: gated"),
+            None
+        );
+        assert_eq!(
+            error("new: delay(std::time::Duration::from_millis(1))"),
+            None
+        );
+        assert_eq!(error("meow: panic_on(1), meow: panic_on(2)"), None);
+        assert_eq!(
+            error("hiss: once"),
+            Some(vec!["no method `hiss` in this anonymous impl".to_string()])
+        );
+        assert_eq!(
+            error("new: once, new: panic_on(1)"),
+            Some(vec![
+                "cannot count calls of `new` because it has no `self` receiver".to_string(),
+                "cannot count calls of `new` because it has no `self` receiver".to_string(),
+            ])
+        );
+        assert_eq!(
+            error("expect feed(eq(\"fish\"))"),
+            Some(vec![
+                "`feed` takes 2 argument(s), but 1 matcher(s) are given".to_string()
+            ])
+        );
+        assert_eq!(
+            error("meow: gated"),
+            Some(vec![
+                "`meow` cannot be gated because it is not an async method taking `self`"
+                    .to_string()
+            ])
+        );
+        assert_eq!(
+            error("expect feed(eq(\"fish\"), _) once, expect feed(eq(\"tuna\"), _)"),
+            Some(vec!["duplicate `expect` for `feed`".to_string()])
+        );
+        assert_eq!(
+            error("meow: once, expect meow() times(2)"),
+            Some(vec![
+                "duplicate expected number of calls for `meow`".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn spy_serde() {
+        let result = syn::parse_str::<LetDefault>("let my_mock = Cat, spy(serde)");
+        if cfg!(feature = "serde") {
+            let attr = result.unwrap();
+            assert!(attr.options.spy);
+            assert!(attr.options.serde);
+        } else {
+            assert_eq!(
+                result.err().unwrap().to_string(),
+                "`spy(serde)` requires the `serde` feature of `anonymous-trait`"
+            );
+        }
+        let result = syn::parse_str::<LetDefault>("let my_mock = Cat, spy(json)");
+        assert_eq!(result.err().unwrap().to_string(), "expected `serde`");
+    }
+
+    #[test]
+    fn tracing() {
+        let result = syn::parse_str::<LetDefault>("let my_mock = Cat, tracing");
+        if cfg!(feature = "tracing") {
+            assert!(result.unwrap().options.tracing);
+        } else {
+            assert_eq!(
+                result.err().unwrap().to_string(),
+                "the `tracing` option requires the `tracing` feature of `anonymous-trait`"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_option() {
+        let result = syn::parse_str::<LetDefault>("let my_mock = Cat, unknown");
+        assert_eq!(result.err().unwrap().to_string(), "unknown option");
+    }
+}
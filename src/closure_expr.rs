@@ -1,5 +1,6 @@
 use proc_macro2::{Group, Span, TokenStream, TokenTree};
 use quote::{quote, ToTokens};
+use syn::spanned::Spanned as _;
 
 pub(crate) fn generate(target: &syn::Type, input: &syn::ImplItemFn) -> TokenStream {
     let mut args = vec![];
@@ -10,7 +11,10 @@ pub(crate) fn generate(target: &syn::Type, input: &syn::ImplItemFn) -> TokenStre
             .as_ref()
             .map(|(and_token, _lifetime)| and_token);
         let mutability = &receiver.mutability;
-        args.push(quote! { __anonymous_trait_state: #reference #mutability #target })
+        let state = state_ident(receiver.self_token.span);
+        // located at the receiver, so that suggestions such as adding `mut` point at it
+        let target = respan(target.to_token_stream(), receiver.span());
+        args.push(quote! { #state: #reference #mutability #target })
     };
     input.sig.inputs.iter().for_each(|arg| {
         let syn::FnArg::Typed(pat_type) = arg else {
@@ -54,19 +58,53 @@ pub(crate) fn generate(target: &syn::Type, input: &syn::ImplItemFn) -> TokenStre
     }
 }
 
+/// The closure parameter replacing `self`, located at the original `self` so that errors point at
+/// it, and resolved at the macro definition so that it cannot collide with user identifiers.
+pub(crate) fn state_ident(span: Span) -> syn::Ident {
+    syn::Ident::new(
+        "__anonymous_trait_state",
+        Span::mixed_site().located_at(span),
+    )
+}
+
+/// Replaces every `self` in the tokens with the state.
+pub(crate) fn replace_self_in(tokens: TokenStream) -> TokenStream {
+    tokens.into_iter().map(replace_self).collect()
+}
+
+fn respan(tokens: TokenStream, span: Span) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            if let TokenTree::Group(group) = &token {
+                let mut group = Group::new(group.delimiter(), respan(group.stream(), span));
+                group.set_span(span);
+                token = TokenTree::Group(group);
+            } else {
+                token.set_span(token.span().located_at(span));
+            }
+            token
+        })
+        .collect()
+}
+
 fn replace_self(token: TokenTree) -> TokenTree {
     match token {
         TokenTree::Ident(ident) => {
             if ident == "self" {
-                TokenTree::Ident(syn::Ident::new("__anonymous_trait_state", Span::call_site()))
+                TokenTree::Ident(state_ident(ident.span()))
             } else {
                 TokenTree::Ident(ident)
             }
         }
-        TokenTree::Group(group) => TokenTree::Group(Group::new(
-            group.delimiter(),
-            group.stream().into_iter().map(replace_self).collect(),
-        )),
+        TokenTree::Group(group) => {
+            let mut replaced = Group::new(
+                group.delimiter(),
+                group.stream().into_iter().map(replace_self).collect(),
+            );
+            replaced.set_span(group.span());
+            TokenTree::Group(replaced)
+        }
         TokenTree::Punct(punct) => TokenTree::Punct(punct),
         TokenTree::Literal(literal) => TokenTree::Literal(literal),
     }
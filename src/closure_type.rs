@@ -1,12 +1,16 @@
 use proc_macro2::TokenStream;
 use quote::quote;
 
-pub(crate) fn generate(target: &syn::Type, input: &syn::ImplItemFn) -> TokenStream {
+use crate::attr_syntax::Options;
+
+pub(crate) fn generate(
+    options: &Options,
+    target: &syn::Type,
+    input: &syn::ImplItemFn,
+) -> TokenStream {
     let mut args = vec![];
-    let bound = input
-        .sig
-        .asyncness
-        .map_or_else(|| quote! {}, |_| quote! { + Send });
+    let send = options.requires_send(&input.sig).then(|| quote! { + Send });
+    let sync = options.sync.then(|| quote! { + Sync });
     if let Some(receiver) = input.sig.receiver() {
         let reference = receiver
             .reference
@@ -45,8 +49,17 @@ pub(crate) fn generate(target: &syn::Type, input: &syn::ImplItemFn) -> TokenStre
             quote! { #arrow #ty }
         }
     };
+    let fn_trait = match (
+        options.is_lock_free(&input.sig),
+        options.is_async_closure(&input.sig),
+    ) {
+        (true, true) => quote!(std::ops::AsyncFn),
+        (false, true) => quote!(std::ops::AsyncFnMut),
+        (true, false) => quote!(Fn),
+        (false, false) => quote!(FnMut),
+    };
     quote! {
-        FnMut(#(#args),*) #output #bound
+        #fn_trait(#(#args),*) #output #send #sync
     }
 }
 
@@ -65,7 +78,7 @@ mod tests {
                 "meow".to_string()
             }
         };
-        let actual = generate(&target, &input);
+        let actual = generate(&Options::default(), &target, &input);
         let expected = quote! {
             FnMut()
         };
@@ -80,7 +93,7 @@ mod tests {
                 "meow".to_string()
             }
         };
-        let actual = generate(&target, &input);
+        let actual = generate(&Options::default(), &target, &input);
         let expected = quote! {
             FnMut(&Cat)
         };
@@ -95,7 +108,7 @@ mod tests {
                 "meow".to_string()
             }
         };
-        let actual = generate(&target, &input);
+        let actual = generate(&Options::default(), &target, &input);
         let expected = quote! {
             FnMut(&mut Cat)
         };
@@ -110,7 +123,7 @@ mod tests {
                 "meow".to_string()
             }
         };
-        let actual = generate(&target, &input);
+        let actual = generate(&Options::default(), &target, &input);
         let expected = quote! {
             FnMut(Cat)
         };
@@ -125,7 +138,7 @@ mod tests {
                 format!("{}: meow {}", name, count)
             }
         };
-        let actual = generate(&target, &input);
+        let actual = generate(&Options::default(), &target, &input);
         let expected = quote! {
             FnMut(String, usize)
         };
@@ -140,7 +153,7 @@ mod tests {
                 format!("{}: meow {}", name, count)
             }
         };
-        let actual = generate(&target, &input);
+        let actual = generate(&Options::default(), &target, &input);
         let expected = quote! {
             FnMut(&str, usize)
         };
@@ -155,7 +168,7 @@ mod tests {
                 format!("{}: meow {}", name, count)
             }
         };
-        let actual = generate(&target, &input);
+        let actual = generate(&Options::default(), &target, &input);
         let expected = quote! {
             FnMut(&Cat, String, usize)
         };
@@ -170,7 +183,7 @@ mod tests {
                 format!("{}: meow {}", name, count)
             }
         };
-        let actual = generate(&target, &input);
+        let actual = generate(&Options::default(), &target, &input);
         let expected = quote! {
             FnMut(&Cat, &str, usize)
         };
@@ -185,7 +198,7 @@ mod tests {
                 "meow".to_string()
             }
         };
-        let actual = generate(&target, &input);
+        let actual = generate(&Options::default(), &target, &input);
         let expected = quote! {
             FnMut() -> String
         };
@@ -200,10 +213,107 @@ mod tests {
                 "meow"
             }
         };
-        let actual = generate(&target, &input);
+        let actual = generate(&Options::default(), &target, &input);
         let expected = quote! {
             FnMut() -> &'static str
         };
         assert_eq!(actual.to_string(), expected.to_string());
     }
+
+    #[test]
+    fn lock_free() {
+        let options = Options {
+            lock_free: true,
+            ..Default::default()
+        };
+        let target = parse_quote!(Cat);
+        let input = parse_quote! {
+            fn meow(&self) -> String {
+                "meow".to_string()
+            }
+        };
+        let actual = generate(&options, &target, &input);
+        let expected = quote! {
+            Fn(&Cat) -> String
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+        let input = parse_quote! {
+            fn set_name(&mut self, name: String) {
+                self.name = name;
+            }
+        };
+        let actual = generate(&options, &target, &input);
+        let expected = quote! {
+            FnMut(&mut Cat, String)
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn send_sync() {
+        let target = parse_quote!(Cat);
+        let input = parse_quote! {
+            fn meow(&self) -> String {
+                "meow".to_string()
+            }
+        };
+        let options = Options {
+            sync: true,
+            ..Default::default()
+        };
+        let actual = generate(&options, &target, &input);
+        let expected = quote! {
+            FnMut(&Cat) -> String + Send + Sync
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn async_without_send() {
+        let target = parse_quote!(Cat);
+        let input = parse_quote! {
+            async fn meow(&self) -> String {
+                "meow".to_string()
+            }
+        };
+        let options = Options {
+            send: Some(false),
+            ..Default::default()
+        };
+        let actual = generate(&options, &target, &input);
+        let expected = quote! {
+            FnMut(&Cat) -> String
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn async_closures() {
+        let options = Options {
+            async_closures: true,
+            lock_free: true,
+            ..Default::default()
+        };
+        let target = parse_quote!(Cat);
+        let input = parse_quote! {
+            async fn meow(&self) -> String {
+                "meow".to_string()
+            }
+        };
+        let actual = generate(&options, &target, &input);
+        let expected = quote! {
+            std::ops::AsyncFn(&Cat) -> String + Send
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+        let input = parse_quote! {
+            async fn set_name(&mut self, name: String) {
+                self.name = name;
+            }
+        };
+        let actual = generate(&options, &target, &input);
+        let expected = quote! {
+            std::ops::AsyncFnMut(&mut Cat, String) + Send
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
 }
@@ -1,40 +1,152 @@
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote};
 
-use crate::{attr_syntax::LetDefault, impl_syntax::AnonymousImpl};
+use crate::{
+    attr_syntax::LetDefault,
+    impl_syntax::{cfg_attrs, lint_attrs, AnonymousImpl},
+};
 
-pub(crate) fn generate(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
+/// Generates the locals borrowed by the implementor, followed by the binding initialized with a
+/// block scoping the generated `items`, so that any number of anonymous impls can coexist.
+pub(crate) fn generate(
+    attr: &LetDefault,
+    input: &AnonymousImpl,
+    items: TokenStream,
+) -> TokenStream {
     let let_token = &attr.let_token;
     let pat_ident = &attr.pat_ident;
     let default = &attr.expr;
     let target = input.target();
     let ident = input.struct_name(attr);
-    let state_ident = format_ident!("__anonymous_trait_state");
+    let state_field = format_ident!("__anonymous_trait_state");
+    let state_ident = syn::Ident::new("__anonymous_trait_state", Span::mixed_site());
     let let_closures = input.methods().map(|method| {
-        let ident = format_ident!("__anonymous_trait__{}", &method.sig.ident);
-        let closure = crate::closure_expr::generate(target, method);
+        let ident = closure_ident(&method.sig.ident);
+        let closure = crate::closure_expr::generate(target, &input.closure_method(method));
+        let asyncness = attr
+            .options
+            .is_async_closure(&method.sig)
+            .then(|| quote!(async));
+        let lock_free = attr.options.is_lock_free(&method.sig);
+        let mutability =
+            (!lock_free && !attr.options.factory && !attr.options.swappable).then(|| quote!(mut));
+        let cfgs = cfg_attrs(&method.attrs);
+        let lints = lint_attrs(&method.attrs);
+        // In factory mode, the implementors share the locked closure.
+        let closure = if attr.options.factory && !lock_free {
+            quote!(std::sync::Mutex::new(#asyncness #closure))
+        } else {
+            quote!(#asyncness #closure)
+        };
         quote! {
+            #(#cfgs)*
+            #(#lints)*
             #[allow(non_snake_case)]
-            let mut #ident = #closure;
+            let #mutability #ident = #closure;
         }
     });
     let closures = input.methods().map(|method| {
         let method_ident = &method.sig.ident;
-        let ident = format_ident!("__anonymous_trait__{}", &method.sig.ident);
+        let ident = closure_ident(method_ident);
+        let cfgs = cfg_attrs(&method.attrs);
+        if attr.options.swappable {
+            quote! {
+                #(#cfgs)*
+                #method_ident: std::sync::Mutex::new(Box::new(#ident)),
+            }
+        } else if attr.options.is_lock_free(&method.sig) || attr.options.factory {
+            quote! {
+                #(#cfgs)*
+                #method_ident: &#ident,
+            }
+        } else {
+            quote! {
+                #(#cfgs)*
+                #method_ident: std::sync::Mutex::new(&mut #ident),
+            }
+        }
+    });
+    let gates = crate::gate::gated_methods(attr, input).map(|sig| {
+        let gate_ident = crate::gate::gate_ident(&sig.ident);
         quote! {
-            #method_ident: std::sync::Mutex::new(&mut #ident),
+            #gate_ident: Default::default(),
         }
     });
+    let let_scripts = crate::scripted::let_scripts(attr, input);
+    let calls = attr.options.spy.then(|| {
+        quote! {
+            __anonymous_trait_calls: std::sync::Mutex::new(Vec::new()),
+        }
+    });
+    let counters = crate::packed_struct::counted_methods(attr, input).map(|sig| {
+        let counter_ident = crate::packed_struct::counter_ident(&sig.ident);
+        quote! {
+            #counter_ident: std::sync::atomic::AtomicUsize::new(0),
+        }
+    });
+    // A named struct must be visible after the binding.
+    let (outer_items, items) = if attr.options.name.is_some() {
+        (items, quote! {})
+    } else {
+        (quote! {}, items)
+    };
+    if attr.options.factory {
+        return quote! {
+            #outer_items
+            #let_scripts
+            #(#let_closures)*
+            #let_token #pat_ident = {
+                #items
+                |#state_ident: #target| #ident {
+                    #state_field: #state_ident,
+                    #calls
+                    #(#counters)*
+                    #(#gates)*
+                    #(#closures)*
+                }
+            };
+        };
+    }
+    let (let_state, state) = match &attr.options.shared {
+        Some(handle) if attr.options.shares_across_threads() => (
+            quote!(let #handle = std::sync::Arc::new(std::sync::Mutex::new(#default));),
+            quote!(std::sync::Arc::clone(&#handle)),
+        ),
+        Some(handle) => (
+            quote!(let #handle = std::rc::Rc::new(std::cell::RefCell::new(#default));),
+            quote!(std::rc::Rc::clone(&#handle)),
+        ),
+        None => (
+            quote!(let mut #state_ident = #default;),
+            quote!(&mut #state_ident),
+        ),
+    };
     quote! {
-        let mut #state_ident = #default;
+        #outer_items
+        #let_state
+        #let_scripts
         #(#let_closures)*
-        #let_token #pat_ident = #ident {
-            #state_ident: &mut #state_ident,
-            #(#closures)*
+        #let_token #pat_ident = {
+            #items
+            #ident {
+                #state_field: #state,
+                #calls
+                #(#counters)*
+                #(#gates)*
+                #(#closures)*
+            }
         };
     }
 }
 
+/// The local holding the closure of the method, which cannot collide with user identifiers.
+fn closure_ident(method: &syn::Ident) -> syn::Ident {
+    syn::Ident::new(
+        &format!("__anonymous_trait__{}", method),
+        Span::mixed_site(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use pretty_assertions::assert_eq;
@@ -50,11 +162,13 @@ mod tests {
         let input = parse_quote! {
             impl Something for Cat {}
         };
-        let actual = generate(&attr, &input);
+        let actual = generate(&attr, &input, quote! {});
         let expected = quote! {
             let mut __anonymous_trait_state = Cat::default();
-            let my_mock = my_mock__Something {
-                __anonymous_trait_state: &mut __anonymous_trait_state,
+            let my_mock = {
+                my_mock__Something {
+                    __anonymous_trait_state: &mut __anonymous_trait_state,
+                }
             };
         };
         assert_eq!(actual.to_string(), expected.to_string());
@@ -68,11 +182,13 @@ mod tests {
         let input = parse_quote! {
             impl Something for Cat {}
         };
-        let actual = generate(&attr, &input);
+        let actual = generate(&attr, &input, quote! {});
         let expected = quote! {
             let mut __anonymous_trait_state = Cat::default();
-            let mut my_mock =my_mock__Something {
-                __anonymous_trait_state: &mut __anonymous_trait_state,
+            let mut my_mock = {
+                my_mock__Something {
+                    __anonymous_trait_state: &mut __anonymous_trait_state,
+                }
             };
         };
         assert_eq!(actual.to_string(), expected.to_string());
@@ -93,7 +209,7 @@ mod tests {
                 }
             }
         };
-        let actual = generate(&attr, &input);
+        let actual = generate(&attr, &input, quote! {});
         let expected = quote! {
             let mut __anonymous_trait_state = Cat::default();
             #[allow(non_snake_case)]
@@ -104,10 +220,12 @@ mod tests {
             let mut __anonymous_trait__change_name = |__anonymous_trait_state: &mut Cat, name: String| {
                 __anonymous_trait_state.name = name;
             };
-            let my_mock = my_mock__Something {
-                __anonymous_trait_state: &mut __anonymous_trait_state,
-                meow: std::sync::Mutex::new(&mut __anonymous_trait__meow),
-                change_name: std::sync::Mutex::new(&mut __anonymous_trait__change_name),
+            let my_mock = {
+                my_mock__Something {
+                    __anonymous_trait_state: &mut __anonymous_trait_state,
+                    meow: std::sync::Mutex::new(&mut __anonymous_trait__meow),
+                    change_name: std::sync::Mutex::new(&mut __anonymous_trait__change_name),
+                }
             };
         };
         assert_eq!(actual.to_string(), expected.to_string());
@@ -122,11 +240,271 @@ mod tests {
             impl Something for Cat {
             }
         };
-        let actual = generate(&attr, &input);
+        let actual = generate(&attr, &input, quote! {});
         let expected = quote! {
             let mut __anonymous_trait_state = Cat::new(aaa);
-            let my_mock = my_mock__Something {
-                __anonymous_trait_state: &mut __anonymous_trait_state,
+            let my_mock = {
+                my_mock__Something {
+                    __anonymous_trait_state: &mut __anonymous_trait_state,
+                }
+            };
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn spy() {
+        let attr = parse_quote! {
+            let my_mock = Cat::default(), spy
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+            }
+        };
+        let actual = generate(&attr, &input, quote! {});
+        let expected = quote! {
+            let mut __anonymous_trait_state = Cat::default();
+            #[allow(non_snake_case)]
+            let mut __anonymous_trait__meow = |__anonymous_trait_state: &Cat| -> String {
+                "meow".to_string()
+            };
+            let my_mock = {
+                my_mock__Something {
+                    __anonymous_trait_state: &mut __anonymous_trait_state,
+                    __anonymous_trait_calls: std::sync::Mutex::new(Vec::new()),
+                    __anonymous_trait_count_meow: std::sync::atomic::AtomicUsize::new(0),
+                    meow: std::sync::Mutex::new(&mut __anonymous_trait__meow),
+                }
+            };
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn resolve_self() {
+        let attr = parse_quote! {
+            let my_mock = Cat::default()
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                const LIMIT: usize = 3;
+                fn limit(&self) -> usize {
+                    Self::LIMIT
+                }
+            }
+        };
+        let actual = generate(&attr, &input, quote! {});
+        let expected = quote! {
+            let mut __anonymous_trait_state = Cat::default();
+            #[allow(non_snake_case)]
+            let mut __anonymous_trait__limit = |__anonymous_trait_state: &Cat| -> usize {
+                (3)
+            };
+            let my_mock = {
+                my_mock__Something {
+                    __anonymous_trait_state: &mut __anonymous_trait_state,
+                    limit: std::sync::Mutex::new(&mut __anonymous_trait__limit),
+                }
+            };
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn lock_free() {
+        let attr = parse_quote! {
+            let my_mock = Cat::default(), lock_free
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+                fn change_name(&mut self, name: String) {
+                    self.name = name;
+                }
+            }
+        };
+        let actual = generate(&attr, &input, quote! {});
+        let expected = quote! {
+            let mut __anonymous_trait_state = Cat::default();
+            #[allow(non_snake_case)]
+            let __anonymous_trait__meow = |__anonymous_trait_state: &Cat| -> String {
+                "meow".to_string()
+            };
+            #[allow(non_snake_case)]
+            let mut __anonymous_trait__change_name = |__anonymous_trait_state: &mut Cat, name: String| {
+                __anonymous_trait_state.name = name;
+            };
+            let my_mock = {
+                my_mock__Something {
+                    __anonymous_trait_state: &mut __anonymous_trait_state,
+                    meow: &__anonymous_trait__meow,
+                    change_name: std::sync::Mutex::new(&mut __anonymous_trait__change_name),
+                }
+            };
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn async_closures() {
+        let attr = parse_quote! {
+            let my_mock = Cat::default(), async_closures
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                async fn meow(&self) -> String {
+                    fetch().await
+                }
+            }
+        };
+        let actual = generate(&attr, &input, quote! {});
+        let expected = quote! {
+            let mut __anonymous_trait_state = Cat::default();
+            #[allow(non_snake_case)]
+            let __anonymous_trait__meow = async |__anonymous_trait_state: &Cat| -> String {
+                fetch().await
+            };
+            let my_mock = {
+                my_mock__Something {
+                    __anonymous_trait_state: &mut __anonymous_trait_state,
+                    meow: &__anonymous_trait__meow,
+                }
+            };
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn method_attributes() {
+        let attr = parse_quote! {
+            let my_mock = Cat::default()
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                #[inline]
+                #[allow(unused_variables)]
+                #[cfg(feature = "purr")]
+                fn purr(&self, volume: u8) {}
+            }
+        };
+        let actual = generate(&attr, &input, quote! {});
+        let expected = quote! {
+            let mut __anonymous_trait_state = Cat::default();
+            #[cfg(feature = "purr")]
+            #[allow(unused_variables)]
+            #[allow(non_snake_case)]
+            let mut __anonymous_trait__purr = |__anonymous_trait_state: &Cat, volume: u8| {};
+            let my_mock = {
+                my_mock__Something {
+                    __anonymous_trait_state: &mut __anonymous_trait_state,
+                    #[cfg(feature = "purr")]
+                    purr: std::sync::Mutex::new(&mut __anonymous_trait__purr),
+                }
+            };
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn scoped_items() {
+        let attr = parse_quote! {
+            let my_mock = Cat::default()
+        };
+        let input = parse_quote! {
+            impl Something for Cat {}
+        };
+        let actual = generate(&attr, &input, quote! { struct my_mock__Something; });
+        let expected = quote! {
+            let mut __anonymous_trait_state = Cat::default();
+            let my_mock = {
+                struct my_mock__Something;
+                my_mock__Something {
+                    __anonymous_trait_state: &mut __anonymous_trait_state,
+                }
+            };
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn named() {
+        let attr = parse_quote! {
+            let my_mock = Cat::default(), as CatMock
+        };
+        let input = parse_quote! {
+            impl Something for Cat {}
+        };
+        let actual = generate(&attr, &input, quote! { struct CatMock; });
+        let expected = quote! {
+            struct CatMock;
+            let mut __anonymous_trait_state = Cat::default();
+            let my_mock = {
+                CatMock {
+                    __anonymous_trait_state: &mut __anonymous_trait_state,
+                }
+            };
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn factory() {
+        let attr = parse_quote! {
+            let make_cat = _, factory, lock_free
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+                fn change_name(&mut self, name: String) {
+                    self.name = name;
+                }
+            }
+        };
+        let actual = generate(&attr, &input, quote! {});
+        let expected = quote! {
+            #[allow(non_snake_case)]
+            let __anonymous_trait__meow = |__anonymous_trait_state: &Cat| -> String {
+                "meow".to_string()
+            };
+            #[allow(non_snake_case)]
+            let __anonymous_trait__change_name = std::sync::Mutex::new(
+                |__anonymous_trait_state: &mut Cat, name: String| {
+                    __anonymous_trait_state.name = name;
+                }
+            );
+            let make_cat = {
+                |__anonymous_trait_state: Cat| make_cat__Something {
+                    __anonymous_trait_state: __anonymous_trait_state,
+                    meow: &__anonymous_trait__meow,
+                    change_name: &__anonymous_trait__change_name,
+                }
+            };
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn shared() {
+        let attr = parse_quote! {
+            let my_mock = Cat::default(), shared(cat_state)
+        };
+        let input = parse_quote! {
+            impl Something for Cat {}
+        };
+        let actual = generate(&attr, &input, quote! {});
+        let expected = quote! {
+            let cat_state = std::rc::Rc::new(std::cell::RefCell::new(Cat::default()));
+            let my_mock = {
+                my_mock__Something {
+                    __anonymous_trait_state: std::rc::Rc::clone(&cat_state),
+                }
             };
         };
         assert_eq!(actual.to_string(), expected.to_string());
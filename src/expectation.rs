@@ -0,0 +1,240 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use crate::{
+    attr_syntax::{LetDefault, Times},
+    impl_syntax::AnonymousImpl,
+};
+
+/// Generates the call count accessors, and the `Drop` implementation and `verify_expectations`
+/// method verifying the expected number of calls.
+pub(crate) fn generate(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
+    if !attr.options.counts_calls() {
+        return quote! {};
+    }
+    let struct_name = input.struct_name(attr);
+    let trait_name = &input
+        .trait_
+        .segments
+        .last()
+        .expect("not empty trait path")
+        .ident;
+    let generics = crate::packed_struct::impl_generics(attr, input);
+    let where_clause = crate::packed_struct::where_clause(attr, input);
+    let struct_generics = crate::packed_struct::ty_generics(attr, input);
+    let accessors = crate::packed_struct::counted_methods(attr, input).map(|sig| {
+        let method_ident = &sig.ident;
+        let accessor_ident = accessor_ident(method_ident);
+        let counter_ident = crate::packed_struct::counter_ident(method_ident);
+        quote! {
+            fn #accessor_ident(&self) -> usize {
+                self.#counter_ident.load(std::sync::atomic::Ordering::SeqCst)
+            }
+        }
+    });
+    let checks = attr.options.expectations().map(|(method, times)| {
+        let counter_ident = crate::packed_struct::counter_ident(method);
+        let (condition, expected, expected_value) = match times {
+            Times::Exactly(n) => (
+                quote!(count == expected),
+                "to be called exactly {} time(s)",
+                quote!(#n),
+            ),
+            Times::AtLeast(n) => (
+                quote!(count >= expected),
+                "to be called at least {} time(s)",
+                quote!(#n),
+            ),
+            Times::AtMost(n) => (
+                quote!(count <= expected),
+                "to be called at most {} time(s)",
+                quote!(#n),
+            ),
+            Times::Once => (
+                quote!(count == expected),
+                "to be called once",
+                quote!(1usize),
+            ),
+            Times::Never => (
+                quote!(count == expected),
+                "not to be called",
+                quote!(0usize),
+            ),
+        };
+        let message = format!(
+            "expected `{}::{}` {}, but it was called {{}} time(s)",
+            trait_name, method, expected
+        );
+        let args = if expected.contains("{}") {
+            quote!(expected, count)
+        } else {
+            quote!(count)
+        };
+        quote! {
+            {
+                let count = self.#counter_ident.load(std::sync::atomic::Ordering::SeqCst);
+                let expected: usize = #expected_value;
+                assert!(#condition, #message, #args);
+            }
+        }
+    });
+    let has_expectations = attr.options.expectations().next().is_some();
+    // The `Drop` implementation keeps the captured variables borrowed until the end of the scope,
+    // so the implementor is consumed to verify the expectations earlier.
+    let verify = has_expectations.then(|| {
+        quote! {
+            fn verify_expectations(self) {
+                drop(self);
+            }
+        }
+    });
+    let drop = has_expectations.then(|| {
+        quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                #generics
+            > Drop for #struct_name<#struct_generics> #where_clause {
+                fn drop(&mut self) {
+                    if std::thread::panicking() {
+                        return;
+                    }
+                    #(#checks)*
+                }
+            }
+        }
+    });
+    quote! {
+        #[allow(non_camel_case_types, dead_code)]
+        impl <
+            #generics
+        > #struct_name<#struct_generics> #where_clause {
+            #(#accessors)*
+            #verify
+        }
+        #drop
+    }
+}
+
+/// The name of the method returning the number of calls of the method.
+pub(crate) fn accessor_ident(method: &syn::Ident) -> syn::Ident {
+    format_ident!("{}_call_count", method)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn disabled() {
+        let attr = parse_quote! {
+            let my_mock = Cat
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        assert!(actual.is_empty());
+    }
+
+    #[test]
+    fn spy_only() {
+        let attr = parse_quote! {
+            let my_mock = Cat, spy
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types, dead_code)]
+            impl <
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String,
+            > my_mock__Something<'__anonymous_trait_state, meow> {
+                fn meow_call_count(&self) -> usize {
+                    self.__anonymous_trait_count_meow.load(std::sync::atomic::Ordering::SeqCst)
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn expectations() {
+        let attr = parse_quote! {
+            let my_mock = Cat, meow: times(2), set_name: never
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+                fn set_name(&mut self, name: String) {
+                    self.name = name;
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types, dead_code)]
+            impl <
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String,
+                set_name: FnMut(&mut Cat, String),
+            > my_mock__Something<'__anonymous_trait_state, meow, set_name> {
+                fn meow_call_count(&self) -> usize {
+                    self.__anonymous_trait_count_meow.load(std::sync::atomic::Ordering::SeqCst)
+                }
+                fn set_name_call_count(&self) -> usize {
+                    self.__anonymous_trait_count_set_name.load(std::sync::atomic::Ordering::SeqCst)
+                }
+                fn verify_expectations(self) {
+                    drop(self);
+                }
+            }
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String,
+                set_name: FnMut(&mut Cat, String),
+            > Drop for my_mock__Something<'__anonymous_trait_state, meow, set_name> {
+                fn drop(&mut self) {
+                    if std::thread::panicking() {
+                        return;
+                    }
+                    {
+                        let count = self.__anonymous_trait_count_meow.load(std::sync::atomic::Ordering::SeqCst);
+                        let expected: usize = 2;
+                        assert!(
+                            count == expected,
+                            "expected `Something::meow` to be called exactly {} time(s), but it was called {} time(s)",
+                            expected,
+                            count
+                        );
+                    }
+                    {
+                        let count = self.__anonymous_trait_count_set_name.load(std::sync::atomic::Ordering::SeqCst);
+                        let expected: usize = 0usize;
+                        assert!(
+                            count == expected,
+                            "expected `Something::set_name` not to be called, but it was called {} time(s)",
+                            count
+                        );
+                    }
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+}
@@ -0,0 +1,188 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote_spanned;
+use syn::parse_quote_spanned;
+
+use crate::attr_syntax::{Fault, LetDefault};
+
+/// Whether a fault depends on the number of the call, which is then bound to [`call_ident`].
+pub(crate) fn needs_call_number(attr: &LetDefault, sig: &syn::Signature) -> bool {
+    sig.receiver().is_some()
+        && attr
+            .options
+            .faults(&sig.ident)
+            .any(|fault| !matches!(fault, Fault::Delay(_)))
+}
+
+/// The local holding the 1-based number of the current call.
+pub(crate) fn call_ident() -> syn::Ident {
+    syn::Ident::new("call", Span::mixed_site())
+}
+
+/// The statements injecting the faults of the method, run before dispatching the call. The values
+/// of `fail_on` are returned by [`fail`] instead.
+pub(crate) fn inject(
+    attr: &LetDefault,
+    trait_name: &syn::Ident,
+    sig: &syn::Signature,
+) -> Vec<syn::Stmt> {
+    let call = call_ident();
+    attr.options
+        .faults(&sig.ident)
+        .filter(|fault| sig.receiver().is_some() || matches!(fault, Fault::Delay(_)))
+        .filter_map(|fault| match fault {
+            Fault::PanicOn(n) => {
+                let message = format!(
+                    "fault injected into `{}::{}` on call {{}}",
+                    trait_name, sig.ident
+                );
+                Some(parse_quote_spanned! {Span::mixed_site()=>
+                    if #call == #n {
+                        panic!(#message, #call);
+                    }
+                })
+            }
+            Fault::FailOn(..) => None,
+            Fault::Delay(duration) if sig.asyncness.is_some() => {
+                let delay = delay(duration);
+                Some(parse_quote_spanned! {Span::mixed_site()=>
+                    #delay.await;
+                })
+            }
+            Fault::Delay(duration) => Some(parse_quote_spanned! {Span::mixed_site()=>
+                std::thread::sleep(#duration);
+            }),
+        })
+        .collect()
+}
+
+/// Whether the method returns the values of `fail_on` on some calls.
+pub(crate) fn fails(attr: &LetDefault, sig: &syn::Signature) -> bool {
+    sig.receiver().is_some()
+        && attr
+            .options
+            .faults(&sig.ident)
+            .any(|fault| matches!(fault, Fault::FailOn(..)))
+}
+
+/// The dispatched expression, evaluating to the values of `fail_on` on their calls instead, so that
+/// they are recorded like the returned values.
+pub(crate) fn fail(attr: &LetDefault, sig: &syn::Signature, expr: syn::Expr) -> syn::Expr {
+    if !fails(attr, sig) {
+        return expr;
+    }
+    let call = call_ident();
+    let branches = attr
+        .options
+        .faults(&sig.ident)
+        .filter_map(|fault| match fault {
+            Fault::FailOn(n, value) => Some(quote_spanned! {Span::mixed_site()=>
+                if #call == #n {
+                    #value
+                }
+            }),
+            _ => None,
+        });
+    parse_quote_spanned! {Span::mixed_site()=>
+        #(#branches)else* else {
+            #expr
+        }
+    }
+}
+
+/// A future completing after the duration, woken by a thread sleeping until then, so that it does
+/// not depend on the async runtime.
+fn delay(duration: &syn::Expr) -> TokenStream {
+    quote_spanned! {Span::mixed_site()=>
+        {
+            let deadline = std::time::Instant::now() + #duration;
+            let waker = std::sync::Arc::new(std::sync::Mutex::new(None::<std::task::Waker>));
+            std::future::poll_fn(move |cx| {
+                if std::time::Instant::now() >= deadline {
+                    return std::task::Poll::Ready(());
+                }
+                if waker.lock().unwrap().replace(cx.waker().clone()).is_none() {
+                    let waker = std::sync::Arc::clone(&waker);
+                    std::thread::spawn(move || {
+                        std::thread::sleep(deadline.saturating_duration_since(std::time::Instant::now()));
+                        if let Some(waker) = waker.lock().unwrap().take() {
+                            waker.wake();
+                        }
+                    });
+                }
+                std::task::Poll::Pending
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use quote::{quote, ToTokens as _};
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn inject_faults() {
+        let attr = parse_quote! {
+            let my_mock = Cat, meow: panic_on(3), meow: fail_on(1, String::new())
+        };
+        let sig = parse_quote! {
+            fn meow(&self) -> String
+        };
+        assert!(needs_call_number(&attr, &sig));
+        let actual = inject(&attr, &parse_quote!(Cat), &sig);
+        let expected = quote! {
+            if call == 3 {
+                panic!("fault injected into `Cat::meow` on call {}", call);
+            }
+        };
+        assert_eq!(
+            quote!(#(#actual)*).to_string(),
+            expected.to_token_stream().to_string()
+        );
+    }
+
+    #[test]
+    fn fail_on_calls() {
+        let attr = parse_quote! {
+            let my_mock = Cat, meow: fail_on(1, String::new()), meow: fail_on(3, "hiss".into())
+        };
+        let sig = parse_quote! {
+            fn meow(&self) -> String
+        };
+        assert!(fails(&attr, &sig));
+        let actual = fail(&attr, &sig, parse_quote!(meow()));
+        let expected = quote! {
+            if call == 1 {
+                String::new()
+            } else if call == 3 {
+                "hiss".into()
+            } else {
+                meow()
+            }
+        };
+        assert_eq!(actual.to_token_stream().to_string(), expected.to_string());
+        let sig = parse_quote! {
+            fn meow() -> String
+        };
+        assert!(!fails(&attr, &sig));
+    }
+
+    #[test]
+    fn delay_without_receiver() {
+        let attr = parse_quote! {
+            let my_mock = Cat, meow: delay(std::time::Duration::from_millis(1))
+        };
+        let sig = parse_quote! {
+            fn meow()
+        };
+        assert!(!needs_call_number(&attr, &sig));
+        let actual = inject(&attr, &parse_quote!(Cat), &sig);
+        let expected = quote! {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        };
+        assert_eq!(quote!(#(#actual)*).to_string(), expected.to_string());
+    }
+}
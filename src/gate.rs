@@ -0,0 +1,241 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use crate::{
+    attr_syntax::{LetDefault, MethodOptionKind},
+    impl_syntax::AnonymousImpl,
+};
+
+/// Generates the gate type and the `{method}_gate()` accessors of the gated methods.
+pub(crate) fn generate(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
+    if gated_methods(attr, input).next().is_none() {
+        return quote! {};
+    }
+    let struct_name = input.struct_name(attr);
+    let gate_name = input.gate_struct_name(attr);
+    let generics = crate::packed_struct::impl_generics(attr, input);
+    let where_clause = crate::packed_struct::where_clause(attr, input);
+    let struct_generics = crate::packed_struct::ty_generics(attr, input);
+    let accessors = gated_methods(attr, input).map(|sig| {
+        let accessor_ident = accessor_ident(&sig.ident);
+        let gate_ident = gate_ident(&sig.ident);
+        quote! {
+            fn #accessor_ident(&self) -> &#gate_name {
+                &self.#gate_ident
+            }
+        }
+    });
+    quote! {
+        #[allow(non_camel_case_types)]
+        #[derive(Default)]
+        struct #gate_name {
+            open: std::sync::atomic::AtomicBool,
+            // Calls waiting at the closed gate take a ticket in order of arrival, and are let
+            // through once their ticket is released.
+            tickets: std::sync::atomic::AtomicUsize,
+            released: std::sync::atomic::AtomicUsize,
+            waiting: std::sync::atomic::AtomicUsize,
+            passed: std::sync::atomic::AtomicUsize,
+            // also guards the other fields, so that no wake-up is lost
+            wakers: std::sync::Mutex<Vec<std::task::Waker>>,
+            // The tickets of the cancelled calls, which are skipped when released. Only locked
+            // while `wakers` is.
+            abandoned: std::sync::Mutex<Vec<usize>>,
+        }
+
+        #[allow(non_camel_case_types, dead_code)]
+        impl #gate_name {
+            // Lets every call through until the gate is closed.
+            fn open(&self) {
+                let mut wakers = self.wakers.lock().unwrap();
+                self.open.store(true, std::sync::atomic::Ordering::SeqCst);
+                self.released.fetch_max(
+                    self.tickets.load(std::sync::atomic::Ordering::SeqCst),
+                    std::sync::atomic::Ordering::SeqCst,
+                );
+                self.abandoned.lock().unwrap().clear();
+                wakers.drain(..).for_each(std::task::Waker::wake);
+            }
+
+            // Holds the next calls.
+            fn close(&self) {
+                let _wakers = self.wakers.lock().unwrap();
+                self.open.store(false, std::sync::atomic::Ordering::SeqCst);
+            }
+
+            // Lets the call waiting the longest through, or the next one if none is waiting.
+            fn step(&self) {
+                let mut wakers = self.wakers.lock().unwrap();
+                let mut abandoned = self.abandoned.lock().unwrap();
+                loop {
+                    let ticket = self.released.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    match abandoned.iter().position(|abandoned| *abandoned == ticket) {
+                        Some(index) => {
+                            abandoned.swap_remove(index);
+                        }
+                        None => break,
+                    }
+                }
+                wakers.drain(..).for_each(std::task::Waker::wake);
+            }
+
+            // The number of calls waiting at the gate.
+            fn waiting(&self) -> usize {
+                self.waiting.load(std::sync::atomic::Ordering::SeqCst)
+            }
+
+            // The number of calls let through the gate.
+            fn passed(&self) -> usize {
+                self.passed.load(std::sync::atomic::Ordering::SeqCst)
+            }
+
+            // Waits until at least `n` calls are waiting at the gate.
+            async fn until_waiting(&self, n: usize) {
+                self.until(|gate| gate.waiting() >= n).await
+            }
+
+            // Waits until at least `n` calls have been let through the gate.
+            async fn until_passed(&self, n: usize) {
+                self.until(|gate| gate.passed() >= n).await
+            }
+
+            async fn until(&self, condition: impl Fn(&Self) -> bool) {
+                std::future::poll_fn(|cx| {
+                    let mut wakers = self.wakers.lock().unwrap();
+                    if condition(self) {
+                        return std::task::Poll::Ready(());
+                    }
+                    wakers.push(cx.waker().clone());
+                    std::task::Poll::Pending
+                })
+                .await
+            }
+
+            async fn pass(&self) {
+                // Gives back the ticket of a call cancelled while waiting, so that the next step
+                // is not spent on it.
+                struct Waiting<'a> {
+                    gate: &'a #gate_name,
+                    ticket: Option<usize>,
+                }
+                impl Drop for Waiting<'_> {
+                    fn drop(&mut self) {
+                        let Some(ticket) = self.ticket else {
+                            return;
+                        };
+                        let mut wakers = self.gate.wakers.lock().unwrap();
+                        self.gate.waiting.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                        if ticket >= self.gate.released.load(std::sync::atomic::Ordering::SeqCst) {
+                            self.gate.abandoned.lock().unwrap().push(ticket);
+                        }
+                        wakers.drain(..).for_each(std::task::Waker::wake);
+                    }
+                }
+                let mut waiting = Waiting {
+                    gate: self,
+                    ticket: None,
+                };
+                std::future::poll_fn(|cx| {
+                    let mut wakers = self.wakers.lock().unwrap();
+                    let open = self.open.load(std::sync::atomic::Ordering::SeqCst);
+                    if waiting.ticket.is_none() && !open {
+                        waiting.ticket =
+                            Some(self.tickets.fetch_add(1, std::sync::atomic::Ordering::SeqCst));
+                        self.waiting.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                        wakers.drain(..).for_each(std::task::Waker::wake);
+                    }
+                    match waiting.ticket {
+                        Some(ticket)
+                            if !open
+                                && ticket >= self.released.load(std::sync::atomic::Ordering::SeqCst) =>
+                        {
+                            wakers.push(cx.waker().clone());
+                            return std::task::Poll::Pending;
+                        }
+                        Some(_) => {
+                            waiting.ticket = None;
+                            self.waiting.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                        }
+                        None => {}
+                    }
+                    self.passed.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    wakers.drain(..).for_each(std::task::Waker::wake);
+                    std::task::Poll::Ready(())
+                })
+                .await
+            }
+        }
+
+        #[allow(non_camel_case_types, dead_code)]
+        impl <
+            #generics
+        > #struct_name<#struct_generics> #where_clause {
+            #(#accessors)*
+        }
+    }
+}
+
+/// The statement waiting at the gate of the method, if it is gated.
+pub(crate) fn pass(attr: &LetDefault, sig: &syn::Signature) -> Option<syn::Stmt> {
+    if !is_gated(attr, sig) {
+        return None;
+    }
+    let gate_ident = gate_ident(&sig.ident);
+    Some(syn::parse_quote! {
+        self.#gate_ident.pass().await;
+    })
+}
+
+/// The gated methods, which are async methods taking `self`.
+pub(crate) fn gated_methods<'a>(
+    attr: &'a LetDefault,
+    input: &'a AnonymousImpl,
+) -> impl Iterator<Item = &'a syn::Signature> {
+    input.signatures().filter(|sig| is_gated(attr, sig))
+}
+
+/// The name of the method returning the gate of the method.
+pub(crate) fn accessor_ident(method: &syn::Ident) -> syn::Ident {
+    format_ident!("{}_gate", method)
+}
+
+/// The name of the field holding the gate of the method.
+pub(crate) fn gate_ident(method: &syn::Ident) -> syn::Ident {
+    format_ident!("__anonymous_trait_gate_{}", method)
+}
+
+fn is_gated(attr: &LetDefault, sig: &syn::Signature) -> bool {
+    is_gatable(sig)
+        && attr.options.methods.iter().any(|option| {
+            matches!(option.kind, MethodOptionKind::Gated) && option.method == sig.ident
+        })
+}
+
+/// Whether the method can wait at a gate, which is awaited while `self` is borrowed.
+pub(crate) fn is_gatable(sig: &syn::Signature) -> bool {
+    sig.asyncness.is_some() && sig.receiver().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use quote::ToTokens as _;
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn pass_gate() {
+        let attr = parse_quote! {
+            let my_mock = Cat, meow: gated
+        };
+        let sig = parse_quote! {
+            async fn meow(&self) -> String
+        };
+        let actual = pass(&attr, &sig).unwrap();
+        let expected = quote! {
+            self.__anonymous_trait_gate_meow.pass().await;
+        };
+        assert_eq!(actual.to_token_stream().to_string(), expected.to_string());
+    }
+}
@@ -1,4 +1,5 @@
-use quote::format_ident;
+use proc_macro2::Span;
+use quote::{format_ident, quote, ToTokens as _};
 use syn::{
     parse::{Parse, ParseStream},
     spanned::Spanned,
@@ -13,6 +14,13 @@ pub(crate) struct AnonymousImpl {
     pub for_token: Token![for],
     /// The Self type of the impl.
     pub item_impl: syn::ItemImpl,
+    /// Methods declared without a body, which are forwarded to the wrapped value.
+    pub forwarded: Vec<syn::TraitItemFn>,
+    /// Required methods of the trait missing from the impl, filled in `stub` mode.
+    pub stubbed: Vec<syn::TraitItemFn>,
+    /// Associated types and consts of the trait missing from the impl, forwarded to the wrapped
+    /// value.
+    pub inherited: Vec<syn::ImplItem>,
 }
 
 impl Parse for AnonymousImpl {
@@ -21,14 +29,182 @@ impl Parse for AnonymousImpl {
         let Some((_, trait_, for_token)) = item_impl.trait_.clone() else {
             return Err(syn::Error::new(item_impl.self_ty.span(), "expected trait"));
         };
+        validate(&item_impl)?;
+        let forwarded = item_impl
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::ImplItem::Verbatim(tokens) => syn::parse2::<syn::TraitItemFn>(tokens.clone())
+                    .ok()
+                    .filter(|method| method.default.is_none()),
+                _ => None,
+            })
+            .collect();
         Ok(Self {
             trait_,
             for_token,
             item_impl,
+            forwarded,
+            stubbed: vec![],
+            inherited: vec![],
         })
     }
 }
 
+/// Rejects the constructs the generated code cannot support, explaining how to fix them.
+fn validate(item_impl: &syn::ItemImpl) -> syn::Result<()> {
+    let mut errors = vec![];
+    if let Some(unsafety) = &item_impl.unsafety {
+        errors.push(syn::Error::new(
+            unsafety.span,
+            "`unsafe impl` is not supported in an anonymous impl\n\
+             help: implement the unsafe trait for a hand-written struct, upholding its safety contract",
+        ));
+    }
+    if let Some(param) = item_impl.generics.params.first() {
+        errors.push(syn::Error::new(
+            param.span(),
+            "generic parameters are not supported in an anonymous impl\n\
+             help: use concrete types, since the impl is instantiated only once",
+        ));
+    }
+    for item in &item_impl.items {
+        match item {
+            syn::ImplItem::Fn(method) => validate_method(method, &mut errors),
+            syn::ImplItem::Macro(item) => errors.push(syn::Error::new(
+                item.mac.path.span(),
+                "macro invocations are not supported in an anonymous impl\n\
+                 help: write the items they expand to in the impl",
+            )),
+            syn::ImplItem::Verbatim(tokens)
+                if syn::parse2::<syn::TraitItemFn>(tokens.clone())
+                    .is_ok_and(|method| method.default.is_none()) => {}
+            syn::ImplItem::Type(_) | syn::ImplItem::Const(_) => {}
+            item => errors.push(syn::Error::new(
+                item.span(),
+                "unsupported item in an anonymous impl",
+            )),
+        }
+    }
+    errors
+        .into_iter()
+        .reduce(|mut error, next| {
+            error.combine(next);
+            error
+        })
+        .map_or(Ok(()), Err)
+}
+
+fn validate_method(method: &syn::ImplItemFn, errors: &mut Vec<syn::Error>) {
+    let sig = &method.sig;
+    if is_async_trait_expanded(sig) {
+        errors.push(syn::Error::new(
+            sig.ident.span(),
+            "this impl has already been expanded by `#[async_trait]`\n\
+             help: place `#[anonymous_trait]` above `#[async_trait]`",
+        ));
+        return;
+    }
+    if let Some(param) = sig.generics.type_params().next() {
+        errors.push(syn::Error::new(
+            param.ident.span(),
+            format!(
+                "generic method `{}` is not supported because closures cannot be generic\n\
+                 help: use a concrete type or `&dyn Trait`",
+                sig.ident
+            ),
+        ));
+    }
+    if let Some(param) = sig.generics.const_params().next() {
+        errors.push(syn::Error::new(
+            param.ident.span(),
+            format!(
+                "const generic method `{}` is not supported because closures cannot be generic",
+                sig.ident
+            ),
+        ));
+    }
+    for arg in &sig.inputs {
+        let syn::FnArg::Typed(pat_type) = arg else {
+            continue;
+        };
+        if let syn::Type::ImplTrait(impl_trait) = pat_type.ty.as_ref() {
+            errors.push(syn::Error::new(
+                impl_trait.impl_token.span,
+                "`impl Trait` arguments are not supported because closures cannot be generic\n\
+                 help: use `&dyn Trait` or `Box<dyn Trait>`",
+            ));
+        }
+        if !matches!(pat_type.pat.as_ref(), syn::Pat::Ident(pat_ident) if pat_ident.subpat.is_none())
+        {
+            errors.push(syn::Error::new(
+                pat_type.pat.span(),
+                "only identifiers are supported as argument patterns in an anonymous impl\n\
+                 help: bind the argument to a name and destructure it in the body",
+            ));
+        }
+    }
+    if sig.receiver().is_none() {
+        if let Some(self_token) = find_self_value(method.block.to_token_stream()) {
+            errors.push(syn::Error::new(
+                self_token.span(),
+                format!(
+                    "`self` cannot be used in `{}` because it has no `self` receiver\n\
+                     help: add a receiver such as `&self` to the method in the trait",
+                    sig.ident
+                ),
+            ));
+        }
+    }
+}
+
+/// Finds `self` used as a value, skipping paths such as `self::module`.
+fn find_self_value(tokens: proc_macro2::TokenStream) -> Option<proc_macro2::Ident> {
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            proc_macro2::TokenTree::Ident(ident) if ident == "self" => {
+                let is_path = matches!(
+                    tokens.peek(),
+                    Some(proc_macro2::TokenTree::Punct(punct)) if punct.as_char() == ':'
+                );
+                if !is_path {
+                    return Some(ident);
+                }
+            }
+            proc_macro2::TokenTree::Group(group) => {
+                if let Some(ident) = find_self_value(group.stream()) {
+                    return Some(ident);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `#[cfg]` attributes, which are applied to every item generated for a method.
+pub(crate) fn cfg_attrs(attrs: &[syn::Attribute]) -> impl Iterator<Item = &syn::Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("cfg"))
+}
+
+/// Lint attributes such as `#[allow]`, which are forwarded to the closure of a method.
+pub(crate) fn lint_attrs(attrs: &[syn::Attribute]) -> impl Iterator<Item = &syn::Attribute> {
+    attrs.iter().filter(|attr| {
+        ["allow", "expect", "warn", "deny", "forbid"]
+            .iter()
+            .any(|lint| attr.path().is_ident(lint))
+    })
+}
+
+/// Whether `#[async_trait]` has already turned the method into one returning a boxed future, which
+/// happens when it is placed above `#[anonymous_trait]`.
+fn is_async_trait_expanded(sig: &syn::Signature) -> bool {
+    sig.generics
+        .lifetimes()
+        .any(|param| param.lifetime.ident == "async_trait")
+}
+
 impl AnonymousImpl {
     pub(crate) fn methods(&self) -> impl Iterator<Item = &syn::ImplItemFn> {
         self.item_impl.items.iter().filter_map(|item| match item {
@@ -36,10 +212,253 @@ impl AnonymousImpl {
             _ => None,
         })
     }
+    /// Whether the closure of the method is stored as a `dyn` closure instead of a generic closure
+    /// type, which is the case for a named struct, for swappable closures and for methods with
+    /// `#[cfg]`, because generic arguments cannot be configured out.
+    pub(crate) fn is_type_erased(&self, attr: &LetDefault, method: &syn::ImplItemFn) -> bool {
+        attr.options.name.is_some()
+            || attr.options.swappable
+            || cfg_attrs(&method.attrs).next().is_some()
+    }
+    /// Methods stored as a generic closure type.
+    pub(crate) fn generic_methods<'a>(
+        &'a self,
+        attr: &'a LetDefault,
+    ) -> impl Iterator<Item = &'a syn::ImplItemFn> {
+        self.methods()
+            .filter(|method| !self.is_type_erased(attr, method))
+    }
+    /// Signatures of all the methods, including the forwarded and stubbed ones.
+    pub(crate) fn signatures(&self) -> impl Iterator<Item = &syn::Signature> {
+        self.methods()
+            .map(|method| &method.sig)
+            .chain(self.forwarded.iter().map(|method| &method.sig))
+            .chain(self.stubbed.iter().map(|method| &method.sig))
+    }
+    /// Whether the futures must be `Send` if the impl is annotated with `#[async_trait]`, which is
+    /// not the case for `#[async_trait(?Send)]`.
+    pub(crate) fn async_trait_send(&self) -> Option<bool> {
+        self.item_impl.attrs.iter().find_map(|attr| {
+            let segment = attr.path().segments.last()?;
+            if segment.ident != "async_trait" {
+                return None;
+            }
+            Some(!matches!(
+                &attr.meta,
+                syn::Meta::List(list) if list.tokens.to_string().replace(' ', "") == "?Send"
+            ))
+        })
+    }
+    /// Associated types and consts, which are copied to the generated impl.
+    pub(crate) fn associated_items(&self) -> impl Iterator<Item = &syn::ImplItem> {
+        self.item_impl
+            .items
+            .iter()
+            .filter(|item| matches!(item, syn::ImplItem::Type(_) | syn::ImplItem::Const(_)))
+            .chain(&self.inherited)
+    }
+    /// The method as a closure sees it, with `Self` replaced by the target type and `Self::Name`
+    /// by the associated type or const of the impl.
+    pub(crate) fn closure_method(&self, method: &syn::ImplItemFn) -> syn::ImplItemFn {
+        let resolve = |name: &syn::Ident| {
+            self.associated_items().find_map(|item| match item {
+                syn::ImplItem::Type(ty) if &ty.ident == name => Some(ty.ty.to_token_stream()),
+                syn::ImplItem::Const(item) if &item.ident == name => {
+                    let expr = &item.expr;
+                    Some(quote!((#expr)))
+                }
+                _ => None,
+            })
+        };
+        let tokens =
+            crate::substitute::replace_self_type(method.to_token_stream(), self.target(), &resolve);
+        syn::parse2(tokens).unwrap_or_else(|_| method.clone())
+    }
+    /// Fills the items of the trait missing from the impl: methods are forwarded to the wrapped
+    /// value in `wrap` mode or stubbed in `stub` mode, and associated items are forwarded to the
+    /// wrapped value. In `wrap` mode, the items with a default are forwarded too, because the
+    /// wrapped value may override them.
+    pub(crate) fn attach_trait(
+        &mut self,
+        item_trait: &syn::ItemTrait,
+        attr: &LetDefault,
+    ) -> syn::Result<()> {
+        let generic_args = match &self
+            .trait_
+            .segments
+            .last()
+            .expect("not empty trait path")
+            .arguments
+        {
+            syn::PathArguments::AngleBracketed(args) => args.args.iter().collect(),
+            _ => vec![],
+        };
+        let mut generic_args = generic_args
+            .into_iter()
+            .filter(|arg| !matches!(arg, syn::GenericArgument::Lifetime(_)));
+        // the generic parameters of the trait are replaced with the arguments in the impl
+        let generics_map = item_trait
+            .generics
+            .params
+            .iter()
+            .filter_map(|param| match param {
+                syn::GenericParam::Type(param) => {
+                    let arg = generic_args
+                        .next()
+                        .map(|arg| arg.to_token_stream())
+                        .or_else(|| param.default.as_ref().map(|ty| ty.to_token_stream()))?;
+                    Some((param.ident.clone(), arg))
+                }
+                syn::GenericParam::Const(param) => {
+                    let arg = generic_args.next()?.to_token_stream();
+                    Some((param.ident.clone(), arg))
+                }
+                syn::GenericParam::Lifetime(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let target = self.target().clone();
+        let trait_ = self.trait_.clone();
+        let implemented = self
+            .signatures()
+            .map(|sig| sig.ident.clone())
+            .chain(self.associated_items().filter_map(|item| match item {
+                syn::ImplItem::Type(ty) => Some(ty.ident.clone()),
+                syn::ImplItem::Const(item) => Some(item.ident.clone()),
+                _ => None,
+            }))
+            .collect::<Vec<_>>();
+        let mut missing = vec![];
+        for item in &item_trait.items {
+            match item {
+                syn::TraitItem::Fn(method)
+                    if (method.default.is_none() || attr.options.wrap)
+                        && !implemented.contains(&method.sig.ident) =>
+                {
+                    let tokens =
+                        crate::substitute::replace_idents(method.to_token_stream(), &generics_map);
+                    let mut method: syn::TraitItemFn = syn::parse2(tokens)?;
+                    name_arguments(&mut method.sig);
+                    if attr.options.wrap {
+                        self.forwarded.push(method);
+                    } else if attr.options.stub.is_some()
+                        || attr.options.returns(&method.sig.ident).is_some()
+                    {
+                        self.stubbed.push(method);
+                    } else {
+                        missing.push(method.sig.ident);
+                    }
+                }
+                syn::TraitItem::Type(ty) if !implemented.contains(&ty.ident) => {
+                    if !attr.options.wrap {
+                        missing.push(ty.ident.clone());
+                        continue;
+                    }
+                    let ident = &ty.ident;
+                    let (impl_generics, ty_generics, where_clause) = ty.generics.split_for_impl();
+                    self.inherited.push(syn::parse_quote! {
+                        type #ident #impl_generics = <#target as #trait_>::#ident #ty_generics #where_clause;
+                    });
+                }
+                syn::TraitItem::Const(item)
+                    if (item.default.is_none() || attr.options.wrap)
+                        && !implemented.contains(&item.ident) =>
+                {
+                    if !attr.options.wrap {
+                        missing.push(item.ident.clone());
+                        continue;
+                    }
+                    let ident = &item.ident;
+                    let ty =
+                        crate::substitute::replace_idents(item.ty.to_token_stream(), &generics_map);
+                    self.inherited.push(syn::parse_quote! {
+                        const #ident: #ty = <#target as #trait_>::#ident;
+                    });
+                }
+                _ => {}
+            }
+        }
+        if !missing.is_empty() {
+            let missing = missing
+                .iter()
+                .map(|ident| format!("`{}`", ident))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(syn::Error::new(
+                self.trait_.span(),
+                format!(
+                    "missing {} in this anonymous impl of `{}`\n\
+                     help: required methods can be generated with the `stub` option or forwarded with `wrap`",
+                    missing, item_trait.ident
+                ),
+            ));
+        }
+        Ok(())
+    }
+    /// Checks that the inherent methods generated on the implementor do not take the name of a
+    /// method of the trait, which they would shadow in method calls.
+    pub(crate) fn validate_accessors(&self, attr: &LetDefault) -> syn::Result<()> {
+        let mut accessors = vec![];
+        if attr.options.swappable {
+            accessors.extend(self.methods().map(|method| {
+                let method = &method.sig.ident;
+                let origin = format!("the setter of `{}` generated by `swappable`", method);
+                (crate::swappable::setter_ident(method), origin)
+            }));
+        }
+        accessors.extend(
+            crate::packed_struct::counted_methods(attr, self).map(|sig| {
+                let origin = format!("the call count accessor of `{}`", sig.ident);
+                (crate::expectation::accessor_ident(&sig.ident), origin)
+            }),
+        );
+        accessors.extend(crate::gate::gated_methods(attr, self).map(|sig| {
+            let origin = format!("the gate accessor of `{}`", sig.ident);
+            (crate::gate::accessor_ident(&sig.ident), origin)
+        }));
+        let mut fixed = vec![];
+        if attr.options.spy {
+            fixed.push(("calls", "`spy`"));
+        }
+        if attr.options.serde {
+            fixed.push(("calls_json", "`spy(serde)`"));
+            fixed.push(("assert_calls_golden", "`spy(serde)`"));
+        }
+        if attr.options.expectations().next().is_some() {
+            fixed.push(("verify_expectations", "the expected number of calls"));
+        }
+        accessors.extend(fixed.into_iter().map(|(name, option)| {
+            (
+                format_ident!("{}", name),
+                format!("the method generated by {}", option),
+            )
+        }));
+        let errors = self.signatures().filter_map(|sig| {
+            let (_, origin) = accessors
+                .iter()
+                .find(|(accessor, _)| accessor == &sig.ident)?;
+            Some(syn::Error::new(
+                sig.ident.span(),
+                format!(
+                    "`{}` has the same name as {}, which would shadow it",
+                    sig.ident, origin
+                ),
+            ))
+        });
+        match errors.reduce(|mut errors, error| {
+            errors.combine(error);
+            errors
+        }) {
+            Some(errors) => Err(errors),
+            None => Ok(()),
+        }
+    }
     pub(crate) fn target(&self) -> &syn::Type {
         self.item_impl.self_ty.as_ref()
     }
     pub(crate) fn struct_name(&self, attr: &LetDefault) -> syn::Ident {
+        if let Some(name) = &attr.options.name {
+            return name.clone();
+        }
         let last = &self
             .trait_
             .segments
@@ -48,4 +467,42 @@ impl AnonymousImpl {
             .ident;
         format_ident!("{}__{}", attr.pat_ident.ident, last)
     }
+    /// The name of the struct recording calls in spy mode.
+    pub(crate) fn call_struct_name(&self, attr: &LetDefault) -> syn::Ident {
+        if let Some(name) = &attr.options.name {
+            return format_ident!("{}Call", name);
+        }
+        format_ident!("{}__Call", self.struct_name(attr))
+    }
+    /// The name of the type wrapping the arguments described in the `tracing` events.
+    pub(crate) fn trace_arg_name(&self, attr: &LetDefault) -> syn::Ident {
+        if let Some(name) = &attr.options.name {
+            return format_ident!("{}TraceArg", name);
+        }
+        format_ident!("{}__TraceArg", self.struct_name(attr))
+    }
+    /// The name of the gate type of the gated methods.
+    pub(crate) fn gate_struct_name(&self, attr: &LetDefault) -> syn::Ident {
+        if let Some(name) = &attr.options.name {
+            return format_ident!("{}Gate", name);
+        }
+        format_ident!("{}__Gate", self.struct_name(attr))
+    }
+}
+
+/// Names the arguments of a method declared in a trait, whose patterns may be `_`, so that the
+/// generated body can refer to them. The receiver is respanned too, because the trait definition
+/// comes from a `macro_rules!` and its `self` is not visible to the generated body otherwise.
+fn name_arguments(sig: &mut syn::Signature) {
+    let mut index = 0usize;
+    sig.inputs.iter_mut().for_each(|arg| match arg {
+        syn::FnArg::Typed(pat_type) => {
+            let ident = format_ident!("__anonymous_trait_arg{}", index);
+            *pat_type.pat = syn::parse_quote!(#ident);
+            index += 1;
+        }
+        syn::FnArg::Receiver(receiver) => {
+            receiver.self_token = Token![self](Span::call_site());
+        }
+    });
 }
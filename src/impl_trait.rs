@@ -1,57 +1,331 @@
-use proc_macro2::TokenStream;
-use quote::quote;
-use syn::parse_quote;
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::{parse_quote, parse_quote_spanned, spanned::Spanned as _};
 
-use crate::{attr_syntax::LetDefault, impl_syntax::AnonymousImpl};
+use crate::{
+    attr_syntax::{LetDefault, Stub},
+    impl_syntax::AnonymousImpl,
+};
 
 pub(crate) fn generate(attr: &LetDefault, mock: &AnonymousImpl) -> TokenStream {
     let trait_ = &mock.trait_;
     let struct_name = mock.struct_name(attr);
-    let lifetime = quote!('__anonymous_trait_state);
-    let generics = mock.methods().map(|method| {
-        let method_ident = &method.sig.ident;
-        let closure_type = crate::closure_type::generate(mock.target(), method);
-        quote! {
-            #method_ident: #closure_type,
-        }
-    });
-    let struct_generics = mock.methods().map(|method| {
-        let method_ident = &method.sig.ident;
-        quote! {
-            #method_ident
-        }
-    });
+    let generics = crate::packed_struct::impl_generics(attr, mock);
+    let where_clause = crate::packed_struct::where_clause(attr, mock);
+    let struct_generics = crate::packed_struct::ty_generics(attr, mock);
+    let attrs = &mock.item_impl.attrs;
+    let associated_items = mock.associated_items();
     let methods = mock.methods().map(|method| {
         let mut method = method.clone();
         let method_ident = &method.sig.ident;
-        let arg_pats = method
+        let arg_pats = arg_pats(&method.sig);
+        let mut stmts = prologue(attr, mock, &method.sig);
+        let span = located_at(method_ident);
+        let state = method
             .sig
-            .inputs
-            .iter()
-            .filter_map(|arg| {
-                let syn::FnArg::Typed(pat_type) = arg else {
-                    return None;
-                };
-                Some(&pat_type.pat)
-            })
-            .collect::<Vec<_>>();
-        method.block.stmts = vec![syn::Stmt::Expr(
-            parse_quote! {
-                self.#method_ident.lock().unwrap()(self.__anonymous_trait_state #(,#arg_pats)*)
+            .receiver()
+            .map(|receiver| state_expr(attr, receiver, span));
+        let args = state
+            .into_iter()
+            .chain(arg_pats.iter().map(|pat| quote!(#pat)));
+        let is_async_closure = attr.options.is_async_closure(&method.sig);
+        let closure = if attr.options.is_lock_free(&method.sig) {
+            quote_spanned!(span=> (self.#method_ident))
+        } else if (attr.options.lock_free || is_async_closure)
+            && is_mut_receiver(&method.sig)
+            && !attr.options.factory
+        {
+            quote_spanned!(span=> self.#method_ident.get_mut().unwrap())
+        } else {
+            quote_spanned!(span=> self.#method_ident.lock().unwrap())
+        };
+        let await_ = is_async_closure.then(|| quote_spanned!(span=> .await));
+        stmts.extend(dispatch(
+            attr,
+            mock,
+            &method.sig,
+            parse_quote_spanned! {located_at(method_ident)=>
+                #closure(#(#args),*) #await_
             },
-            None,
-        )];
+        ));
+        method.block.stmts = crate::trace::instrument(attr, mock, &method.sig, stmts);
         method
     });
+    let erased_errors = mock
+        .methods()
+        .filter(|method| {
+            attr.options.is_async_closure(&method.sig) && mock.is_type_erased(attr, method)
+        })
+        .map(|method| {
+            syn::Error::new(
+                method.sig.ident.span(),
+                "`async_closures` cannot be used for an async method with `#[cfg]` or in a named \
+                 struct, because `AsyncFnMut` closures cannot be type-erased",
+            )
+            .to_compile_error()
+        });
+    // Only the closures of `&self` and `&mut self` methods are reached without a guard, which
+    // would otherwise be held across the await.
+    let locked_errors = mock
+        .methods()
+        .filter(|method| {
+            attr.options.is_async_closure(&method.sig)
+                && !attr.options.is_lock_free(&method.sig)
+                && (!is_mut_receiver(&method.sig) || attr.options.factory)
+        })
+        .map(|method| {
+            syn::Error::new(
+                method.sig.ident.span(),
+                "`async_closures` can only be used for an async method taking `&self`, or `&mut \
+                 self` without `factory`, because the lock of its closure would be held across \
+                 the await",
+            )
+            .to_compile_error()
+        });
+    let async_trait_error = (attr.options.async_closures && mock.async_trait_send() == Some(true))
+        .then(|| {
+            syn::Error::new(
+                trait_.span(),
+                "`async_closures` cannot be used with `#[async_trait]` because the futures of \
+                 `AsyncFnMut` closures are not known to be `Send`\n\
+                 help: use `#[async_trait(?Send)]`",
+            )
+            .to_compile_error()
+        });
+    let forwarded = forwarded(attr, mock, |receiver, span| {
+        state_expr(attr, receiver, span)
+    });
+    let stubbed = stubbed(attr, mock);
     quote! {
+        #(#erased_errors)*
+        #(#locked_errors)*
+        #async_trait_error
+        #(#attrs)*
         #[allow(non_camel_case_types)]
         impl <
-            #lifetime,
-            #(#generics)*
-        > #trait_ for #struct_name<#lifetime #(,#struct_generics)*> {
+            #generics
+        > #trait_ for #struct_name<#struct_generics> #where_clause {
+            #(#associated_items)*
             #(#methods)*
+            #(#forwarded)*
+            #(#stubbed)*
+        }
+    }
+}
+
+/// The methods forwarded to the wrapped value with the `wrap` option, given how the state is
+/// reached from the receiver.
+pub(crate) fn forwarded<'a>(
+    attr: &'a LetDefault,
+    mock: &'a AnonymousImpl,
+    state: impl Fn(&syn::Receiver, Span) -> TokenStream + 'a,
+) -> impl Iterator<Item = syn::ImplItemFn> + 'a {
+    let trait_ = &mock.trait_;
+    let target = mock.target();
+    mock.forwarded.iter().map(move |method| {
+        let method_ident = &method.sig.ident;
+        if !attr.options.wrap {
+            // The error replaces the body, so that the method is not reported as missing too.
+            let error = syn::Error::new(
+                method_ident.span(),
+                "a method without a body is only allowed with the `wrap` option",
+            )
+            .to_compile_error();
+            return impl_item_fn(
+                method,
+                vec![syn::Stmt::Expr(syn::Expr::Verbatim(error), None)],
+            );
+        }
+        let span = located_at(method_ident);
+        let receiver = method.sig.receiver().map(|receiver| state(receiver, span));
+        let args = receiver
+            .into_iter()
+            .chain(arg_pats(&method.sig).into_iter().map(|pat| quote!(#pat)));
+        let await_ = method.sig.asyncness.map(|_| quote_spanned!(span=> .await));
+        let mut stmts = prologue(attr, mock, &method.sig);
+        stmts.extend(dispatch(
+            attr,
+            mock,
+            &method.sig,
+            parse_quote_spanned! {span=>
+                <#target as #trait_>::#method_ident(#(#args),*) #await_
+            },
+        ));
+        impl_item_fn(
+            method,
+            crate::trace::instrument(attr, mock, &method.sig, stmts),
+        )
+    })
+}
+
+/// The required methods missing from the impl, generated in `stub` mode.
+pub(crate) fn stubbed<'a>(
+    attr: &'a LetDefault,
+    mock: &'a AnonymousImpl,
+) -> impl Iterator<Item = syn::ImplItemFn> + 'a {
+    let trait_name = &mock
+        .trait_
+        .segments
+        .last()
+        .expect("not empty trait path")
+        .ident;
+    mock.stubbed.iter().map(move |method| {
+        let body = match attr.options.stub {
+            Some(Stub::Default) => quote!(Default::default()),
+            _ => {
+                let message = format!(
+                    "{}::{} not provided by anonymous impl at {{}}:{{}}",
+                    trait_name, method.sig.ident
+                );
+                quote!(unimplemented!(#message, file!(), line!()))
+            }
+        };
+        let mut stmts = prologue(attr, mock, &method.sig);
+        match attr.options.stub {
+            Some(Stub::Default) => {
+                stmts.extend(dispatch(attr, mock, &method.sig, parse_quote!(#body)))
+            }
+            _ if crate::fault::fails(attr, &method.sig) => {
+                stmts.extend(dispatch(attr, mock, &method.sig, parse_quote!(#body)))
+            }
+            // nothing is returned after the panic
+            _ => stmts.push(syn::Stmt::Expr(parse_quote!(#body), None)),
+        }
+        let mut method = impl_item_fn(
+            method,
+            crate::trace::instrument(attr, mock, &method.sig, stmts),
+        );
+        method
+            .attrs
+            .insert(0, parse_quote!(#[allow(unused_variables)]));
+        method
+    })
+}
+
+fn impl_item_fn(method: &syn::TraitItemFn, stmts: Vec<syn::Stmt>) -> syn::ImplItemFn {
+    syn::ImplItemFn {
+        attrs: method.attrs.clone(),
+        vis: syn::Visibility::Inherited,
+        defaultness: None,
+        sig: method.sig.clone(),
+        block: syn::Block {
+            brace_token: Default::default(),
+            stmts,
+        },
+    }
+}
+
+/// The state passed to the closure for the receiver, which is borrowed from the implementor in
+/// factory mode, where the implementor owns the state, and from the shared handle with `shared`.
+fn state_expr(attr: &LetDefault, receiver: &syn::Receiver, span: Span) -> TokenStream {
+    if attr.options.shared.is_some() {
+        let guard = if attr.options.shares_across_threads() {
+            quote_spanned!(span=> self.__anonymous_trait_state.lock().unwrap())
+        } else if receiver.mutability.is_some() {
+            quote_spanned!(span=> self.__anonymous_trait_state.borrow_mut())
+        } else {
+            quote_spanned!(span=> self.__anonymous_trait_state.borrow())
+        };
+        return match (&receiver.reference, &receiver.mutability) {
+            (Some(_), None) => quote_spanned!(span=> &*#guard),
+            (Some(_), Some(_)) => quote_spanned!(span=> &mut *#guard),
+            (None, _) => syn::Error::new(
+                receiver.self_token.span,
+                "a method taking `self` by value cannot be used with `shared`",
+            )
+            .to_compile_error(),
+        };
+    }
+    if !attr.options.factory {
+        return quote_spanned!(span=> self.__anonymous_trait_state);
+    }
+    let reference = receiver.reference.as_ref().map(|_| quote!(&));
+    let mutability = &receiver.mutability;
+    quote_spanned!(span=> #reference #mutability self.__anonymous_trait_state)
+}
+
+/// A span resolving like the generated code but pointing at the method, so that errors in the
+/// dispatch are reported there.
+fn located_at(method_ident: &syn::Ident) -> Span {
+    Span::call_site().located_at(method_ident.span())
+}
+
+fn is_mut_receiver(sig: &syn::Signature) -> bool {
+    sig.receiver()
+        .is_some_and(|receiver| receiver.reference.is_some() && receiver.mutability.is_some())
+}
+
+/// The names of the arguments, which are bound by identifier patterns as validated by
+/// [`AnonymousImpl`].
+pub(crate) fn arg_pats(sig: &syn::Signature) -> Vec<&syn::Ident> {
+    sig.inputs
+        .iter()
+        .filter_map(|arg| {
+            let syn::FnArg::Typed(pat_type) = arg else {
+                return None;
+            };
+            let syn::Pat::Ident(pat_ident) = pat_type.pat.as_ref() else {
+                return None;
+            };
+            Some(&pat_ident.ident)
+        })
+        .collect()
+}
+
+/// The last statements of a method, evaluating the dispatched expression, or the value of
+/// `fail_on` on its call, and recording the returned value in spy mode.
+fn dispatch(
+    attr: &LetDefault,
+    mock: &AnonymousImpl,
+    sig: &syn::Signature,
+    expr: syn::Expr,
+) -> Vec<syn::Stmt> {
+    let expr = crate::fault::fail(attr, sig, expr);
+    if attr.options.spy && sig.receiver().is_some() {
+        crate::spy::record_return(attr, &mock.call_struct_name(attr), expr)
+    } else {
+        vec![syn::Stmt::Expr(expr, None)]
+    }
+}
+
+/// Statements run before dispatching a call, such as counting and recording it.
+fn prologue(attr: &LetDefault, mock: &AnonymousImpl, sig: &syn::Signature) -> Vec<syn::Stmt> {
+    let method_ident = &sig.ident;
+    let arg_pats = arg_pats(sig);
+    let trait_name = &mock
+        .trait_
+        .segments
+        .last()
+        .expect("not empty trait path")
+        .ident;
+    let mut stmts = vec![];
+    if attr.options.counts_calls() && sig.receiver().is_some() {
+        let counter_ident = crate::packed_struct::counter_ident(method_ident);
+        if crate::fault::needs_call_number(attr, sig) {
+            let call = crate::fault::call_ident();
+            stmts.push(parse_quote! {
+                let #call = self.#counter_ident.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            });
+        } else {
+            stmts.push(parse_quote! {
+                self.#counter_ident.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            });
         }
     }
+    if attr.options.spy && sig.receiver().is_some() {
+        let call_ident = mock.call_struct_name(attr);
+        let method_name = method_ident.to_string();
+        stmts.extend(crate::spy::record_call(
+            attr,
+            &call_ident,
+            &method_name,
+            &arg_pats,
+        ));
+    }
+    stmts.extend(crate::matcher::check(attr, trait_name, sig));
+    stmts.extend(crate::gate::pass(attr, sig));
+    stmts.extend(crate::fault::inject(attr, trait_name, sig));
+    stmts
 }
 
 #[cfg(test)]
@@ -214,4 +488,594 @@ mod tests {
         };
         assert_eq!(actual.to_string(), expected.to_string());
     }
+
+    #[test]
+    fn spy() {
+        let attr = parse_quote! {
+            let my_mock = Cat, spy
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self, volume: u8, count: usize) -> String {
+                    "meow".to_string()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat, u8, usize) -> String,
+            > Something for my_mock__Something<'__anonymous_trait_state, meow> {
+                fn meow(&self, volume: u8, count: usize) -> String {
+                    self.__anonymous_trait_count_meow.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    let call_guard = {
+                        let mut calls = self.__anonymous_trait_calls.lock().unwrap();
+                        calls.push(my_mock__Something__Call {
+                            method: "meow",
+                            args: vec![
+                                (&my_mock__Something__CallValue(&volume)).describe().unwrap_or_else(|| "_".to_string()),
+                                (&my_mock__Something__CallValue(&count)).describe().unwrap_or_else(|| "_".to_string())
+                            ],
+                            ret: None,
+                            panicked: false,
+                        });
+                        my_mock__Something__CallGuard {
+                            calls: &self.__anonymous_trait_calls,
+                            index: calls.len() - 1,
+                        }
+                    };
+                    let ret = self.meow.lock().unwrap()(self.__anonymous_trait_state, volume, count);
+                    call_guard.returned((&my_mock__Something__CallValue(&ret)).describe());
+                    ret
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn forwarded() {
+        let attr = parse_quote! {
+            let my_mock = real_cat, wrap, spy
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+                fn set_name(&mut self, name: String);
+                async fn meow_async(&self) -> String;
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String,
+            > Something for my_mock__Something<'__anonymous_trait_state, meow> {
+                fn meow(&self) -> String {
+                    self.__anonymous_trait_count_meow.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    let call_guard = {
+                        let mut calls = self.__anonymous_trait_calls.lock().unwrap();
+                        calls.push(my_mock__Something__Call {
+                            method: "meow",
+                            args: vec![],
+                            ret: None,
+                            panicked: false,
+                        });
+                        my_mock__Something__CallGuard {
+                            calls: &self.__anonymous_trait_calls,
+                            index: calls.len() - 1,
+                        }
+                    };
+                    let ret = self.meow.lock().unwrap()(self.__anonymous_trait_state);
+                    call_guard.returned((&my_mock__Something__CallValue(&ret)).describe());
+                    ret
+                }
+                fn set_name(&mut self, name: String) {
+                    self.__anonymous_trait_count_set_name.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    let call_guard = {
+                        let mut calls = self.__anonymous_trait_calls.lock().unwrap();
+                        calls.push(my_mock__Something__Call {
+                            method: "set_name",
+                            args: vec![(&my_mock__Something__CallValue(&name)).describe().unwrap_or_else(|| "_".to_string())],
+                            ret: None,
+                            panicked: false,
+                        });
+                        my_mock__Something__CallGuard {
+                            calls: &self.__anonymous_trait_calls,
+                            index: calls.len() - 1,
+                        }
+                    };
+                    let ret = <Cat as Something>::set_name(self.__anonymous_trait_state, name);
+                    call_guard.returned((&my_mock__Something__CallValue(&ret)).describe());
+                    ret
+                }
+                async fn meow_async(&self) -> String {
+                    self.__anonymous_trait_count_meow_async.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    let call_guard = {
+                        let mut calls = self.__anonymous_trait_calls.lock().unwrap();
+                        calls.push(my_mock__Something__Call {
+                            method: "meow_async",
+                            args: vec![],
+                            ret: None,
+                            panicked: false,
+                        });
+                        my_mock__Something__CallGuard {
+                            calls: &self.__anonymous_trait_calls,
+                            index: calls.len() - 1,
+                        }
+                    };
+                    let ret = <Cat as Something>::meow_async(self.__anonymous_trait_state).await;
+                    call_guard.returned((&my_mock__Something__CallValue(&ret)).describe());
+                    ret
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn forwarded_without_wrap() {
+        let attr = parse_quote! {
+            let my_mock = Cat
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn set_name(&mut self, name: String);
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+            > Something for my_mock__Something<'__anonymous_trait_state> {
+                fn set_name(&mut self, name: String) {
+                    ::core::compile_error! {
+                        "a method without a body is only allowed with the `wrap` option"
+                    }
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn stubbed() {
+        let attr = parse_quote! {
+            let my_mock = Cat, stub
+        };
+        let mut input: AnonymousImpl = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+            }
+        };
+        let item_trait = parse_quote! {
+            trait Something {
+                fn meow(&self) -> String;
+                fn set_name(&mut self, _: String);
+                fn purr(&self) {}
+            }
+        };
+        input.attach_trait(&item_trait, &attr).unwrap();
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String,
+            > Something for my_mock__Something<'__anonymous_trait_state, meow> {
+                fn meow(&self) -> String {
+                    self.meow.lock().unwrap()(self.__anonymous_trait_state)
+                }
+                #[allow(unused_variables)]
+                fn set_name(&mut self, __anonymous_trait_arg0: String) {
+                    unimplemented!(
+                        "Something::set_name not provided by anonymous impl at {}:{}",
+                        file!(),
+                        line!()
+                    )
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn stubbed_with_default() {
+        let attr = parse_quote! {
+            let my_mock = Cat, stub(default)
+        };
+        let mut input: AnonymousImpl = parse_quote! {
+            impl Something for Cat {}
+        };
+        let item_trait = parse_quote! {
+            trait Something {
+                async fn meow(&self) -> String;
+            }
+        };
+        input.attach_trait(&item_trait, &attr).unwrap();
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+            > Something for my_mock__Something<'__anonymous_trait_state> {
+                #[allow(unused_variables)]
+                async fn meow(&self) -> String {
+                    Default::default()
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn associated_items() {
+        let attr = parse_quote! {
+            let my_mock = Cat
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                type Item = u8;
+                const LIMIT: usize = 3;
+                fn item(&self) -> Self::Item {
+                    Self::LIMIT as u8
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                item: FnMut(&Cat) -> u8,
+            > Something for my_mock__Something<'__anonymous_trait_state, item> {
+                type Item = u8;
+                const LIMIT: usize = 3;
+                fn item(&self) -> Self::Item {
+                    self.item.lock().unwrap()(self.__anonymous_trait_state)
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn wrap_with_trait() {
+        let attr = parse_quote! {
+            let my_mock = real_store, wrap, from_trait
+        };
+        let mut input: AnonymousImpl = parse_quote! {
+            impl Store<String> for RealStore {
+                fn get(&self, key: &str) -> Option<String> {
+                    None
+                }
+            }
+        };
+        let item_trait = parse_quote! {
+            trait Store<V> {
+                type Error;
+                const NAME: &'static str;
+                fn get(&self, key: &str) -> Option<V>;
+                fn set<'a>(&mut self, key: &'a str, value: V) -> Result<(), Self::Error>;
+                fn describe(&self) -> String {
+                    String::new()
+                }
+            }
+        };
+        input.attach_trait(&item_trait, &attr).unwrap();
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                get: FnMut(&RealStore, &str) -> Option<String>,
+            > Store<String> for my_mock__Store<'__anonymous_trait_state, get> {
+                type Error = <RealStore as Store<String> >::Error;
+                const NAME: &'static str = <RealStore as Store<String> >::NAME;
+                fn get(&self, key: &str) -> Option<String> {
+                    self.get.lock().unwrap()(self.__anonymous_trait_state, key)
+                }
+                fn set<'a>(&mut self, __anonymous_trait_arg0: &'a str, __anonymous_trait_arg1: String) -> Result<(), Self::Error> {
+                    <RealStore as Store<String> >::set(self.__anonymous_trait_state, __anonymous_trait_arg0, __anonymous_trait_arg1)
+                }
+                fn describe(&self) -> String {
+                    <RealStore as Store<String> >::describe(self.__anonymous_trait_state)
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn missing_with_trait() {
+        let attr = parse_quote! {
+            let my_mock = Cat, from_trait
+        };
+        let mut input: AnonymousImpl = parse_quote! {
+            impl Something for Cat {}
+        };
+        let item_trait = parse_quote! {
+            trait Something {
+                type Item;
+                fn meow(&self) -> String;
+                fn purr(&self) {}
+            }
+        };
+        let error = input.attach_trait(&item_trait, &attr).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "missing `Item`, `meow` in this anonymous impl of `Something`\n\
+             help: required methods can be generated with the `stub` option or forwarded with `wrap`"
+        );
+    }
+
+    #[test]
+    fn lock_free() {
+        let attr = parse_quote! {
+            let my_mock = Cat, lock_free
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self, volume: u8) -> String {
+                    "meow".to_string()
+                }
+                fn change_name(&mut self, name: String) {
+                    self.name = name;
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                meow: Fn(&Cat, u8) -> String,
+                change_name: FnMut(&mut Cat, String),
+            > Something for my_mock__Something<'__anonymous_trait_state, meow, change_name> {
+                fn meow(&self, volume: u8) -> String {
+                    (self.meow)(self.__anonymous_trait_state, volume)
+                }
+                fn change_name(&mut self, name: String) {
+                    self.change_name.get_mut().unwrap()(self.__anonymous_trait_state, name)
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn send() {
+        let attr = parse_quote! {
+            let my_mock = Cat, send
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String + Send,
+            > Something for my_mock__Something<'__anonymous_trait_state, meow> where Cat: Send {
+                fn meow(&self) -> String {
+                    self.meow.lock().unwrap()(self.__anonymous_trait_state)
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn async_trait() {
+        let attr = parse_quote! {
+            let my_mock = Cat
+        };
+        let input: AnonymousImpl = parse_quote! {
+            #[async_trait::async_trait]
+            impl Something for Cat {
+                async fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+            }
+        };
+        assert_eq!(input.async_trait_send(), Some(true));
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[async_trait::async_trait]
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String + Send,
+            > Something for my_mock__Something<'__anonymous_trait_state, meow> {
+                async fn meow(&self) -> String {
+                    self.meow.lock().unwrap()(self.__anonymous_trait_state)
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+        let input: AnonymousImpl = parse_quote! {
+            #[async_trait(?Send)]
+            impl Something for Cat {}
+        };
+        assert_eq!(input.async_trait_send(), Some(false));
+    }
+
+    #[test]
+    fn already_expanded_by_async_trait() {
+        let result = syn::parse_str::<AnonymousImpl>(
+            "impl Something for Cat {
+                fn meow<'life0, 'async_trait>(&'life0 self)
+                    -> Pin<Box<dyn Future<Output = String> + Send + 'async_trait>>
+                where 'life0: 'async_trait, Self: 'async_trait {
+                    Box::pin(async move { \"meow\".to_string() })
+                }
+            }",
+        );
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "this impl has already been expanded by `#[async_trait]`\n\
+             help: place `#[anonymous_trait]` above `#[async_trait]`"
+        );
+    }
+
+    #[test]
+    fn async_closures() {
+        let attr = parse_quote! {
+            let my_mock = Cat, async_closures
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                async fn meow(&self) -> String {
+                    fetch().await
+                }
+                async fn set_name(&mut self, name: String) {
+                    self.name = name;
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                meow: std::ops::AsyncFn(&Cat) -> String + Send,
+                set_name: std::ops::AsyncFnMut(&mut Cat, String) + Send,
+            > Something for my_mock__Something<'__anonymous_trait_state, meow, set_name> {
+                async fn meow(&self) -> String {
+                    (self.meow)(self.__anonymous_trait_state).await
+                }
+                async fn set_name(&mut self, name: String) {
+                    self.set_name.get_mut().unwrap()(self.__anonymous_trait_state, name).await
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn async_closures_locked() {
+        let attr = parse_quote! {
+            let make_cat = _, factory, async_closures
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                async fn set_name(&mut self, name: String) {
+                    self.name = name;
+                }
+            }
+        };
+        let actual = generate(&attr, &input).to_string();
+        assert!(actual.contains(
+            "`async_closures` can only be used for an async method taking `&self`, or `&mut self` \
+             without `factory`, because the lock of its closure would be held across the await"
+        ));
+    }
+
+    #[test]
+    fn self_module_path_without_receiver() {
+        let input = syn::parse_str::<AnonymousImpl>(
+            "impl Something for Cat {
+                fn new_name() -> String {
+                    self::default_name()
+                }
+            }",
+        );
+        assert!(input.is_ok());
+        let input = syn::parse_str::<AnonymousImpl>(
+            "impl Something for Cat {
+                fn new_name() -> String {
+                    self.name.clone()
+                }
+            }",
+        );
+        assert_eq!(
+            input.err().unwrap().to_string(),
+            "`self` cannot be used in `new_name` because it has no `self` receiver\n\
+             help: add a receiver such as `&self` to the method in the trait"
+        );
+    }
+
+    #[test]
+    fn factory() {
+        let attr = parse_quote! {
+            let make_cat = _, factory, lock_free
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+                fn change_name(&mut self, name: String) {
+                    self.name = name;
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                meow: Fn(&Cat) -> String,
+                change_name: FnMut(&mut Cat, String),
+            > Something for make_cat__Something<'__anonymous_trait_state, meow, change_name> {
+                fn meow(&self) -> String {
+                    (self.meow)(&self.__anonymous_trait_state)
+                }
+                fn change_name(&mut self, name: String) {
+                    self.change_name.lock().unwrap()(&mut self.__anonymous_trait_state, name)
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn shared() {
+        let attr = parse_quote! {
+            let my_mock = Cat, shared(cat_state)
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+                fn change_name(&mut self, name: String) {
+                    self.name = name;
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            impl <
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String,
+                change_name: FnMut(&mut Cat, String),
+            > Something for my_mock__Something<'__anonymous_trait_state, meow, change_name> {
+                fn meow(&self) -> String {
+                    self.meow.lock().unwrap()(&*self.__anonymous_trait_state.borrow())
+                }
+                fn change_name(&mut self, name: String) {
+                    self.change_name.lock().unwrap()(
+                        &mut *self.__anonymous_trait_state.borrow_mut(),
+                        name
+                    )
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
 }
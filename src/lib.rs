@@ -2,9 +2,20 @@ mod attr_syntax;
 mod closure_expr;
 mod closure_type;
 mod construct;
+mod expectation;
+mod fault;
+mod gate;
 mod impl_syntax;
 mod impl_trait;
+mod matcher;
+mod module_struct;
 mod packed_struct;
+mod scripted;
+mod spy;
+mod stubbable;
+mod substitute;
+mod swappable;
+mod trace;
 
 use attr_syntax::LetDefault;
 use impl_syntax::AnonymousImpl;
@@ -12,15 +23,75 @@ use proc_macro::TokenStream;
 
 #[proc_macro_attribute]
 pub fn anonymous_trait(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr_tokens = proc_macro2::TokenStream::from(attr.clone());
+    let item_tokens = proc_macro2::TokenStream::from(item.clone());
     let attr = syn::parse_macro_input!(attr as LetDefault);
     let input = syn::parse_macro_input!(item as AnonymousImpl);
-    let packed_struct = packed_struct::generate(&attr, &input);
-    let impl_trait = impl_trait::generate(&attr, &input);
-    let construct = construct::generate(&attr, &input);
-    quote::quote! {
+    if attr.options.consults_trait() {
+        return stubbable::request(&input.trait_, attr_tokens, item_tokens).into();
+    }
+    expand(attr, input).into()
+}
+
+/// Exports the trait definition so that `#[anonymous_trait]` with the `stub` or `from_trait`
+/// option can fill in the items missing from the impl.
+#[proc_macro_attribute]
+pub fn stubbable(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item_trait = syn::parse_macro_input!(item as syn::ItemTrait);
+    stubbable::generate(&item_trait).into()
+}
+
+#[doc(hidden)]
+#[proc_macro]
+pub fn __with_trait(input: TokenStream) -> TokenStream {
+    let stubbable::WithTrait {
+        item_trait,
+        attr,
+        mut input,
+    } = syn::parse_macro_input!(input as stubbable::WithTrait);
+    if let Err(error) = input.attach_trait(&item_trait, &attr) {
+        return error.to_compile_error().into();
+    }
+    expand(attr, input).into()
+}
+
+fn expand(mut attr: LetDefault, mut input: AnonymousImpl) -> proc_macro2::TokenStream {
+    // `#[async_trait]` boxes the futures as `Send`, and they hold `&self`, so the generated struct
+    // must be `Sync`. `#[async_trait(?Send)]` does not require even the async methods to be `Send`.
+    match (input.async_trait_send(), attr.options.send) {
+        (Some(true), None | Some(true)) => attr.options.sync = true,
+        (Some(false), None) => attr.options.send = Some(false),
+        _ => {}
+    }
+    let attr = &attr;
+    if let Err(error) = attr.options.validate_methods(&input) {
+        return error.to_compile_error();
+    }
+    if let Err(error) = scripted::attach(attr, &mut input) {
+        return error.to_compile_error();
+    }
+    if let Err(error) = input.validate_accessors(attr) {
+        return error.to_compile_error();
+    }
+    let input = &input;
+    if attr.module.is_some() {
+        return module_struct::generate(attr, input);
+    }
+    let packed_struct = packed_struct::generate(attr, input);
+    let impl_trait = impl_trait::generate(attr, input);
+    let spy = spy::generate(attr, input);
+    let expectation = expectation::generate(attr, input);
+    let swappable = swappable::generate(attr, input);
+    let gate = gate::generate(attr, input);
+    let trace = trace::generate(attr, input);
+    let items = quote::quote! {
         #packed_struct
         #impl_trait
-        #construct
-    }
-    .into()
+        #spy
+        #expectation
+        #swappable
+        #gate
+        #trace
+    };
+    construct::generate(attr, input, items)
 }
@@ -0,0 +1,137 @@
+use proc_macro2::Span;
+use quote::{quote, quote_spanned, ToTokens as _};
+
+use crate::attr_syntax::{LetDefault, Matcher};
+
+/// The statement checking the arguments of a call against the matchers of the method, panicking
+/// with a diff of the expected and actual arguments if any of them does not match.
+pub(crate) fn check(
+    attr: &LetDefault,
+    trait_name: &syn::Ident,
+    sig: &syn::Signature,
+) -> Option<syn::Stmt> {
+    let (_, matchers) = attr
+        .options
+        .matchers()
+        .find(|(method, _)| *method == &sig.ident)?;
+    let args = crate::impl_trait::arg_pats(sig);
+    // resolved at the macro definition, so that they cannot shadow the arguments
+    let mismatched = syn::Ident::new("mismatched", Span::mixed_site());
+    let diff = syn::Ident::new("diff", Span::mixed_site());
+    let expected = syn::Ident::new("expected", Span::mixed_site());
+    let satisfies = syn::Ident::new("satisfies", Span::mixed_site());
+    let checks = args.iter().zip(matchers).map(|(arg, matcher)| {
+        let matched = format!("  {}: {{:?}}\n", arg);
+        let actual = format!("+ {}: {{:?}}\n", arg);
+        let (value, condition, expected_line) = match matcher {
+            Matcher::Eq(value) => {
+                let expected_line = format!("- {}: {{:?}}\n", arg);
+                (
+                    Some(value),
+                    quote!(#arg == #expected),
+                    quote!(format!(#expected_line, #expected)),
+                )
+            }
+            Matcher::Ne(value) => {
+                let expected_line = format!("- {}: ne({{:?}})\n", arg);
+                (
+                    Some(value),
+                    quote!(#arg != #expected),
+                    quote!(format!(#expected_line, #expected)),
+                )
+            }
+            Matcher::Pred(pred) => {
+                let expected_line = format!("- {}: pred({})\n", arg, pred.to_token_stream());
+                // passed directly to a generic function, so that the type of the closure parameter is
+                // inferred
+                (
+                    None,
+                    quote!(#satisfies(&#arg, #pred)),
+                    quote!(String::from(#expected_line)),
+                )
+            }
+            Matcher::Any => {
+                return quote_spanned! {Span::mixed_site()=>
+                    #diff.push_str(&format!(#matched, #arg));
+                };
+            }
+        };
+        let let_expected = value.map(|value| quote!(let #expected = #value;));
+        quote_spanned! {Span::mixed_site()=>
+            #let_expected
+            if #condition {
+                #diff.push_str(&format!(#matched, #arg));
+            } else {
+                #mismatched = true;
+                #diff.push_str(&#expected_line);
+                #diff.push_str(&format!(#actual, #arg));
+            }
+        }
+    });
+    let message = format!(
+        "`{}::{}` was called with unexpected arguments\n{{}}",
+        trait_name, sig.ident
+    );
+    let satisfies_fn = matchers
+        .iter()
+        .any(|matcher| matches!(matcher, Matcher::Pred(_)))
+        .then(|| {
+            quote_spanned! {Span::mixed_site()=>
+                fn #satisfies<T: ?Sized>(value: &T, pred: impl FnOnce(&T) -> bool) -> bool {
+                    pred(value)
+                }
+            }
+        });
+    Some(syn::parse_quote_spanned! {Span::mixed_site()=>
+        {
+            #satisfies_fn
+            let mut #mismatched = false;
+            let mut #diff = String::new();
+            #(#checks)*
+            if #mismatched {
+                panic!(#message, #diff);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn check_arguments() {
+        let attr = parse_quote! {
+            let my_mock = Cat, expect feed(eq("fish"), _) once
+        };
+        let sig = parse_quote! {
+            fn feed(&mut self, food: &str, grams: u32)
+        };
+        let actual = check(&attr, &parse_quote!(Cat), &sig).unwrap();
+        let expected: syn::Stmt = parse_quote! {
+            {
+                let mut mismatched = false;
+                let mut diff = String::new();
+                let expected = "fish";
+                if food == expected {
+                    diff.push_str(&format!("  food: {:?}\n", food));
+                } else {
+                    mismatched = true;
+                    diff.push_str(&format!("- food: {:?}\n", expected));
+                    diff.push_str(&format!("+ food: {:?}\n", food));
+                }
+                diff.push_str(&format!("  grams: {:?}\n", grams));
+                if mismatched {
+                    panic!("`Cat::feed` was called with unexpected arguments\n{}", diff);
+                }
+            }
+        };
+        assert_eq!(
+            actual.to_token_stream().to_string(),
+            expected.to_token_stream().to_string()
+        );
+    }
+}
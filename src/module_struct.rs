@@ -0,0 +1,206 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned, ToTokens as _};
+use syn::parse_quote;
+
+use crate::{
+    attr_syntax::{LetDefault, ModuleStruct},
+    closure_expr::{replace_self_in, state_ident},
+    impl_syntax::AnonymousImpl,
+};
+
+/// Generates a struct owning the state and the captured values, its constructor, and the trait
+/// implementation, for use at module level.
+pub(crate) fn generate(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
+    let ModuleStruct {
+        vis,
+        struct_token,
+        ident,
+        generics,
+        fields,
+    } = attr.module.as_ref().expect("module-level struct");
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let target = input.target();
+    let trait_ = &input.trait_;
+    let default = &attr.expr;
+    let state_field = format_ident!("__anonymous_trait_state");
+    let field_defs = fields.named.iter();
+    let captures = fields
+        .named
+        .iter()
+        .map(|field| field.ident.as_ref().expect("named field"))
+        .collect::<Vec<_>>();
+    let capture_types = fields.named.iter().map(|field| &field.ty);
+    let attrs = &input.item_impl.attrs;
+    let associated_items = input.associated_items();
+    let methods = input.methods().map(|method| {
+        let mut method = method.clone();
+        if method.sig.receiver().is_none() {
+            if let Some(self_ty) = find_self_type(method.sig.to_token_stream()) {
+                // The error replaces the body, so that the method is not reported as missing too.
+                let error = syn::Error::new(
+                    self_ty.span(),
+                    format!(
+                        "`Self` cannot be used in the signature of `{}` in a module-level \
+                         struct, because it is the generated struct, which cannot be built \
+                         without its captured values",
+                        method.sig.ident
+                    ),
+                )
+                .to_compile_error();
+                method.block = parse_quote!({ #error });
+                return method;
+            }
+        }
+        let body = replace_self_in(input.closure_method(&method).block.to_token_stream());
+        // The captured values are bound by reference like the fields of `&self`. As in a closure,
+        // an argument shadows a captured value of the same name.
+        let arg_pats = crate::impl_trait::arg_pats(&method.sig);
+        let captures = captures
+            .iter()
+            .filter(|capture| !arg_pats.contains(capture));
+        let destructure = method.sig.receiver().map(|receiver| {
+            let state = state_ident(receiver.self_token.span);
+            quote! {
+                #[allow(unused_variables)]
+                let Self { #state_field: #state, #(#captures,)* .. } = self;
+            }
+        });
+        method.block = parse_quote!({
+            #destructure
+            #body
+        });
+        method
+    });
+    let forwarded = crate::impl_trait::forwarded(attr, input, |receiver, span| {
+        let reference = receiver.reference.as_ref().map(|_| quote!(&));
+        let mutability = &receiver.mutability;
+        quote_spanned!(span=> #reference #mutability self.__anonymous_trait_state)
+    });
+    let stubbed = crate::impl_trait::stubbed(attr, input);
+    quote! {
+        #vis #struct_token #ident #generics #where_clause {
+            #state_field: #target,
+            #(#field_defs,)*
+        }
+
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #[allow(clippy::new_without_default)]
+            #vis fn new(#(#captures: #capture_types),*) -> Self {
+                Self {
+                    #state_field: #default,
+                    #(#captures,)*
+                }
+            }
+        }
+
+        #(#attrs)*
+        impl #impl_generics #trait_ for #ident #ty_generics #where_clause {
+            #(#associated_items)*
+            #(#methods)*
+            #(#forwarded)*
+            #(#stubbed)*
+        }
+    }
+}
+
+/// Finds `Self` in a signature, where it names the generated struct instead of the state.
+fn find_self_type(tokens: TokenStream) -> Option<proc_macro2::Ident> {
+    tokens.into_iter().find_map(|token| match token {
+        proc_macro2::TokenTree::Ident(ident) if ident == "Self" => Some(ident),
+        proc_macro2::TokenTree::Group(group) => find_self_type(group.stream()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn module_struct() {
+        let attr = parse_quote! {
+            pub struct CatStub { suffix: String } = Cat::default()
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    format!("{}{}", self.name, suffix)
+                }
+                fn set_name(&mut self, suffix: String) {
+                    self.name = suffix;
+                }
+                fn default_name() -> String {
+                    Self::default().name
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            pub struct CatStub {
+                __anonymous_trait_state: Cat,
+                suffix: String,
+            }
+
+            impl CatStub {
+                #[allow(clippy::new_without_default)]
+                pub fn new(suffix: String) -> Self {
+                    Self {
+                        __anonymous_trait_state: Cat::default(),
+                        suffix,
+                    }
+                }
+            }
+
+            impl Something for CatStub {
+                fn meow(&self) -> String {
+                    #[allow(unused_variables)]
+                    let Self { __anonymous_trait_state: __anonymous_trait_state, suffix, .. } = self;
+                    {
+                        format!("{}{}", __anonymous_trait_state.name, suffix)
+                    }
+                }
+                fn set_name(&mut self, suffix: String) {
+                    #[allow(unused_variables)]
+                    let Self { __anonymous_trait_state: __anonymous_trait_state, .. } = self;
+                    {
+                        __anonymous_trait_state.name = suffix;
+                    }
+                }
+                fn default_name() -> String {
+                    {
+                        <Cat>::default().name
+                    }
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn self_in_signature_without_receiver() {
+        let attr = parse_quote! {
+            struct CatStub { suffix: String } = Cat::default()
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn new_cat() -> Self {
+                    Self::default()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            impl Something for CatStub {
+                fn new_cat() -> Self {
+                    ::core::compile_error! {
+                        "`Self` cannot be used in the signature of `new_cat` in a module-level struct, because it is the generated struct, which cannot be built without its captured values"
+                    }
+                }
+            }
+        };
+        assert!(actual.to_string().ends_with(&expected.to_string()));
+    }
+}
@@ -1,38 +1,155 @@
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 
-use crate::{attr_syntax::LetDefault, impl_syntax::AnonymousImpl};
+use crate::{
+    attr_syntax::LetDefault,
+    impl_syntax::{cfg_attrs, AnonymousImpl},
+};
 
 pub(crate) fn generate(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
     let target = input.target();
     let ident = input.struct_name(attr);
     let state_ident = format_ident!("__anonymous_trait_state");
     let state_lifetime = quote!('__anonymous_trait_state);
-    let generics = input.methods().map(|method| {
+    let generics = impl_generics(attr, input);
+    let fields = input.methods().map(|method| {
         let method_ident = &method.sig.ident;
-        let closure_type = crate::closure_type::generate(target, method);
+        let cfgs = cfg_attrs(&method.attrs);
+        let closure_type = if !input.is_type_erased(attr, method) {
+            quote!(#method_ident)
+        } else {
+            let closure_type =
+                crate::closure_type::generate(&attr.options, target, &input.closure_method(method));
+            quote!((dyn #closure_type + #state_lifetime))
+        };
+        if attr.options.swappable {
+            quote! {
+                #(#cfgs)*
+                #method_ident: std::sync::Mutex<Box<#closure_type>>,
+            }
+        } else if attr.options.is_lock_free(&method.sig) {
+            quote! {
+                #(#cfgs)*
+                #method_ident: &#state_lifetime #closure_type,
+            }
+        } else if attr.options.factory {
+            quote! {
+                #(#cfgs)*
+                #method_ident: &#state_lifetime std::sync::Mutex<#closure_type>,
+            }
+        } else {
+            quote! {
+                #(#cfgs)*
+                #method_ident: std::sync::Mutex<&#state_lifetime mut #closure_type>,
+            }
+        }
+    });
+    let calls = attr.options.spy.then(|| {
+        let call_ident = input.call_struct_name(attr);
         quote! {
-            #method_ident: #closure_type,
+            __anonymous_trait_calls: std::sync::Mutex<Vec<#call_ident>>,
         }
     });
-    let fields = input.methods().map(|method| {
-        let method_ident = &method.sig.ident;
+    let counters = counted_methods(attr, input).map(|sig| {
+        let counter_ident = counter_ident(&sig.ident);
+        quote! {
+            #counter_ident: std::sync::atomic::AtomicUsize,
+        }
+    });
+    let gates = crate::gate::gated_methods(attr, input).map(|sig| {
+        let gate_ident = crate::gate::gate_ident(&sig.ident);
+        let gate_name = input.gate_struct_name(attr);
         quote! {
-            #method_ident: std::sync::Mutex<&#state_lifetime mut #method_ident>,
+            #gate_ident: #gate_name,
         }
     });
+    let where_clause = where_clause(attr, input);
+    // In factory mode, each implementor owns its state, and with `shared` it shares the ownership
+    // with the handle.
+    let state_type = if attr.options.shared.is_some() {
+        if attr.options.shares_across_threads() {
+            quote!(std::sync::Arc<std::sync::Mutex<#target>>)
+        } else {
+            quote!(std::rc::Rc<std::cell::RefCell<#target>>)
+        }
+    } else if attr.options.factory {
+        quote!(#target)
+    } else {
+        quote!(& #state_lifetime mut #target)
+    };
     quote! {
         #[allow(non_camel_case_types)]
         struct #ident<
-            #state_lifetime,
-            #(#generics)*
-        > {
-            #state_ident: & #state_lifetime mut #target,
+            #generics
+        > #where_clause {
+            #state_ident: #state_type,
+            #calls
+            #(#counters)*
+            #(#gates)*
             #(#fields)*
         }
     }
 }
 
+/// Generic parameters of the generated struct with their bounds: the state lifetime followed by
+/// one closure type per method without `#[cfg]`.
+pub(crate) fn impl_generics(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
+    let options = &attr.options;
+    let state_lifetime = quote!('__anonymous_trait_state);
+    let generics = input.generic_methods(attr).map(|method| {
+        let method_ident = &method.sig.ident;
+        let closure_type =
+            crate::closure_type::generate(options, input.target(), &input.closure_method(method));
+        quote! {
+            #method_ident: #closure_type,
+        }
+    });
+    quote! {
+        #state_lifetime,
+        #(#generics)*
+    }
+}
+
+/// Methods whose calls are counted by the generated struct.
+pub(crate) fn counted_methods<'a>(
+    attr: &LetDefault,
+    input: &'a AnonymousImpl,
+) -> impl Iterator<Item = &'a syn::Signature> {
+    let counts_calls = attr.options.counts_calls();
+    input
+        .signatures()
+        .filter(move |sig| counts_calls && sig.receiver().is_some())
+}
+
+/// The name of the field counting the calls of the method.
+pub(crate) fn counter_ident(method: &syn::Ident) -> syn::Ident {
+    format_ident!("__anonymous_trait_count_{}", method)
+}
+
+/// Bounds on the state required by the `send` and `sync` options, repeated on every impl of the
+/// generated struct.
+pub(crate) fn where_clause(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
+    let target = input.target();
+    let send = (attr.options.send == Some(true)).then(|| quote!(Send));
+    let sync = attr.options.sync.then(|| quote!(Sync));
+    let bounds = send.into_iter().chain(sync).collect::<Vec<_>>();
+    if bounds.is_empty() {
+        return quote! {};
+    }
+    quote! {
+        where #target: #(#bounds)+*
+    }
+}
+
+/// Generic arguments of the generated struct, in the same order as [`impl_generics`].
+pub(crate) fn ty_generics(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
+    let state_lifetime = quote!('__anonymous_trait_state);
+    let generics = input.generic_methods(attr).map(|method| &method.sig.ident);
+    quote! {
+        #state_lifetime #(,#generics)*
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use pretty_assertions::assert_eq;
@@ -90,4 +207,261 @@ mod tests {
         };
         assert_eq!(actual.to_string(), expected.to_string());
     }
+
+    #[test]
+    fn spy() {
+        let attr = parse_quote! {
+            let my_mock = Cat, spy
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    unimplemented!()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            struct my_mock__Something<
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String,
+            > {
+                __anonymous_trait_state: &'__anonymous_trait_state mut Cat,
+                __anonymous_trait_calls: std::sync::Mutex<Vec<my_mock__Something__Call>>,
+                __anonymous_trait_count_meow: std::sync::atomic::AtomicUsize,
+                meow: std::sync::Mutex<&'__anonymous_trait_state mut meow>,
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn counters() {
+        let attr = parse_quote! {
+            let my_mock = Cat, meow: once
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    unimplemented!()
+                }
+                fn new() -> Self {
+                    unimplemented!()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            struct my_mock__Something<
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String,
+                new: FnMut() -> Cat,
+            > {
+                __anonymous_trait_state: &'__anonymous_trait_state mut Cat,
+                __anonymous_trait_count_meow: std::sync::atomic::AtomicUsize,
+                meow: std::sync::Mutex<&'__anonymous_trait_state mut meow>,
+                new: std::sync::Mutex<&'__anonymous_trait_state mut new>,
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn lock_free() {
+        let attr = parse_quote! {
+            let my_mock = Cat, lock_free
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    unimplemented!()
+                }
+                fn change_name(&mut self, name: String) {
+                    unimplemented!()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            struct my_mock__Something<
+                '__anonymous_trait_state,
+                meow: Fn(&Cat) -> String,
+                change_name: FnMut(&mut Cat, String),
+            > {
+                __anonymous_trait_state: &'__anonymous_trait_state mut Cat,
+                meow: &'__anonymous_trait_state meow,
+                change_name: std::sync::Mutex<&'__anonymous_trait_state mut change_name>,
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn send_sync() {
+        let attr = parse_quote! {
+            let my_mock = Cat, send, sync
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    unimplemented!()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            struct my_mock__Something<
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String + Send + Sync,
+            > where Cat: Send + Sync {
+                __anonymous_trait_state: &'__anonymous_trait_state mut Cat,
+                meow: std::sync::Mutex<&'__anonymous_trait_state mut meow>,
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn cfg() {
+        let attr = parse_quote! {
+            let my_mock = Cat
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    unimplemented!()
+                }
+                #[cfg(feature = "purr")]
+                fn purr(&self, volume: u8) {
+                    unimplemented!()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            struct my_mock__Something<
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String,
+            > {
+                __anonymous_trait_state: &'__anonymous_trait_state mut Cat,
+                meow: std::sync::Mutex<&'__anonymous_trait_state mut meow>,
+                #[cfg(feature = "purr")]
+                purr: std::sync::Mutex<
+                    &'__anonymous_trait_state mut (dyn FnMut(&Cat, u8) + '__anonymous_trait_state)
+                >,
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+        assert_eq!(
+            ty_generics(&attr, &input).to_string(),
+            "'__anonymous_trait_state , meow"
+        );
+    }
+
+    #[test]
+    fn named() {
+        let attr = parse_quote! {
+            let my_mock = Cat, as CatMock, lock_free
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    unimplemented!()
+                }
+                fn change_name(&mut self, name: String) {
+                    unimplemented!()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            struct CatMock<
+                '__anonymous_trait_state,
+            > {
+                __anonymous_trait_state: &'__anonymous_trait_state mut Cat,
+                meow: &'__anonymous_trait_state (dyn Fn(&Cat) -> String + '__anonymous_trait_state),
+                change_name: std::sync::Mutex<
+                    &'__anonymous_trait_state mut (dyn FnMut(&mut Cat, String) + '__anonymous_trait_state)
+                >,
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn swappable() {
+        let attr = parse_quote! {
+            let my_mock = Cat, swappable
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    unimplemented!()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            struct my_mock__Something<
+                '__anonymous_trait_state,
+            > {
+                __anonymous_trait_state: &'__anonymous_trait_state mut Cat,
+                meow: std::sync::Mutex<Box<(dyn FnMut(&Cat) -> String + '__anonymous_trait_state)>>,
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn factory() {
+        let attr = parse_quote! {
+            let make_cat = _, factory
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    unimplemented!()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            struct make_cat__Something<
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String,
+            > {
+                __anonymous_trait_state: Cat,
+                meow: &'__anonymous_trait_state std::sync::Mutex<meow>,
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn shared() {
+        let attr = parse_quote! {
+            let my_mock = Cat, shared(cat_state), send
+        };
+        let input = parse_quote! {
+            impl Something for Cat {}
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            struct my_mock__Something<
+                '__anonymous_trait_state,
+            > where Cat: Send {
+                __anonymous_trait_state: std::sync::Arc<std::sync::Mutex<Cat>>,
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
 }
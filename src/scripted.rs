@@ -0,0 +1,194 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse_quote;
+
+use crate::{
+    attr_syntax::{LetDefault, MethodOptionKind},
+    impl_syntax::{cfg_attrs, AnonymousImpl},
+};
+
+/// Gives the methods with scripted return values a body returning the next value, so that they are
+/// generated like the other methods. They are declared without a body, or omitted with
+/// `from_trait`.
+pub(crate) fn attach(attr: &LetDefault, input: &mut AnonymousImpl) -> syn::Result<()> {
+    let trait_name = input
+        .trait_
+        .segments
+        .last()
+        .expect("not empty trait path")
+        .ident
+        .clone();
+    for option in &attr.options.methods {
+        let MethodOptionKind::Returns(returns) = &option.kind else {
+            continue;
+        };
+        let ident = &option.method;
+        let method = if let Some(index) = input.forwarded.iter().position(|m| &m.sig.ident == ident)
+        {
+            input.forwarded.remove(index)
+        } else if let Some(index) = input.stubbed.iter().position(|m| &m.sig.ident == ident) {
+            input.stubbed.remove(index)
+        } else {
+            return Err(syn::Error::new(
+                ident.span(),
+                format!(
+                    "`{}` has scripted return values, so it must be declared without a body",
+                    ident
+                ),
+            ));
+        };
+        let script = script_ident(ident);
+        let next = if returns.repeat_last {
+            let message = format!("`{}::{}` has no scripted return value", trait_name, ident);
+            quote! {
+                let mut returns = #script.lock().unwrap();
+                if returns.len() > 1 {
+                    returns.pop_front().unwrap()
+                } else {
+                    returns.front().cloned().expect(#message)
+                }
+            }
+        } else {
+            let message = format!(
+                "`{}::{}` was called more times than its {} scripted return value(s)",
+                trait_name,
+                ident,
+                returns.values.len()
+            );
+            quote! {
+                #script.lock().unwrap().pop_front().expect(#message)
+            }
+        };
+        let mut attrs = method.attrs;
+        attrs.insert(0, parse_quote!(#[allow(unused_variables)]));
+        input
+            .item_impl
+            .items
+            .push(syn::ImplItem::Fn(syn::ImplItemFn {
+                attrs,
+                vis: syn::Visibility::Inherited,
+                defaultness: None,
+                sig: method.sig,
+                block: parse_quote!({ #next }),
+            }));
+    }
+    Ok(())
+}
+
+/// Generates the locals holding the remaining return values, borrowed by the closures.
+pub(crate) fn let_scripts(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
+    let scripts = attr.options.methods.iter().filter_map(|option| {
+        let MethodOptionKind::Returns(returns) = &option.kind else {
+            return None;
+        };
+        let method = input
+            .methods()
+            .find(|method| method.sig.ident == option.method)?;
+        let cfgs = cfg_attrs(&method.attrs);
+        let script = script_ident(&option.method);
+        let values = returns.values.iter();
+        Some(quote! {
+            #(#cfgs)*
+            #[allow(non_snake_case)]
+            let #script = std::sync::Mutex::new(std::collections::VecDeque::from([#(#values),*]));
+        })
+    });
+    quote! {
+        #(#scripts)*
+    }
+}
+
+/// The local holding the remaining return values of the method.
+fn script_ident(method: &syn::Ident) -> syn::Ident {
+    syn::Ident::new(
+        &format!("__anonymous_trait__{}_returns", method),
+        Span::mixed_site(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use quote::ToTokens as _;
+
+    use super::*;
+
+    #[test]
+    fn attach_body() {
+        let attr = parse_quote! {
+            let my_mock = Cat, meow: returns ["a".to_string(), "b".to_string()]
+        };
+        let mut input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self, volume: u8) -> String;
+            }
+        };
+        attach(&attr, &mut input).unwrap();
+        assert!(input.forwarded.is_empty());
+        let method = input.methods().next().unwrap();
+        let expected = quote! {
+            #[allow(unused_variables)]
+            fn meow(&self, volume: u8) -> String {
+                __anonymous_trait__meow_returns
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+                    .expect("`Something::meow` was called more times than its 2 scripted return value(s)")
+            }
+        };
+        assert_eq!(method.to_token_stream().to_string(), expected.to_string());
+        let actual = let_scripts(&attr, &input);
+        let expected = quote! {
+            #[allow(non_snake_case)]
+            let __anonymous_trait__meow_returns = std::sync::Mutex::new(
+                std::collections::VecDeque::from(["a".to_string(), "b".to_string()])
+            );
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn repeat_last() {
+        let attr = parse_quote! {
+            let my_mock = Cat, meow: returns [1] repeat_last
+        };
+        let mut input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> u8;
+            }
+        };
+        attach(&attr, &mut input).unwrap();
+        let method = input.methods().next().unwrap();
+        let expected = quote! {
+            #[allow(unused_variables)]
+            fn meow(&self) -> u8 {
+                let mut returns = __anonymous_trait__meow_returns.lock().unwrap();
+                if returns.len() > 1 {
+                    returns.pop_front().unwrap()
+                } else {
+                    returns.front().cloned().expect("`Something::meow` has no scripted return value")
+                }
+            }
+        };
+        assert_eq!(method.to_token_stream().to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn method_with_body() {
+        let attr = parse_quote! {
+            let my_mock = Cat, meow: returns [1]
+        };
+        let mut input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> u8 {
+                    0
+                }
+            }
+        };
+        let error = attach(&attr, &mut input).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "`meow` has scripted return values, so it must be declared without a body"
+        );
+    }
+}
@@ -0,0 +1,456 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+
+use crate::{attr_syntax::LetDefault, impl_syntax::AnonymousImpl};
+
+/// Generates the call record type, the inherent API to read the recorded calls, and the helpers
+/// recording how the calls end.
+pub(crate) fn generate(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
+    if !attr.options.spy {
+        return quote! {};
+    }
+    let struct_name = input.struct_name(attr);
+    let call_ident = input.call_struct_name(attr);
+    let generics = crate::packed_struct::impl_generics(attr, input);
+    let where_clause = crate::packed_struct::where_clause(attr, input);
+    let struct_generics = crate::packed_struct::ty_generics(attr, input);
+    let helpers = helpers(attr, &call_ident);
+    // With `spy(serde)`, the arguments and the returned value are serialized as JSON values rather
+    // than their `Debug` representation, which is kept in `args` and `ret`.
+    let call_struct = if attr.options.serde {
+        quote! {
+            #[allow(non_camel_case_types)]
+            #[derive(Debug, Clone, PartialEq, Eq, ::serde::Serialize)]
+            struct #call_ident {
+                pub method: &'static str,
+                #[serde(skip)]
+                pub args: Vec<String>,
+                #[serde(rename = "args")]
+                pub args_json: Vec<::serde_json::Value>,
+                #[serde(skip)]
+                pub ret: Option<String>,
+                #[serde(rename = "ret")]
+                pub ret_json: Option<::serde_json::Value>,
+                pub panicked: bool,
+            }
+        }
+    } else {
+        quote! {
+            #[allow(non_camel_case_types)]
+            #[derive(Debug, Clone, PartialEq, Eq)]
+            struct #call_ident {
+                pub method: &'static str,
+                pub args: Vec<String>,
+                pub ret: Option<String>,
+                pub panicked: bool,
+            }
+        }
+    };
+    let golden = attr.options.serde.then(golden);
+    quote! {
+        #call_struct
+
+        #helpers
+
+        #[allow(non_camel_case_types, dead_code)]
+        impl <
+            #generics
+        > #struct_name<#struct_generics> #where_clause {
+            fn calls(&self) -> Vec<#call_ident> {
+                self.__anonymous_trait_calls.lock().unwrap().clone()
+            }
+            #golden
+        }
+    }
+}
+
+/// The guard marking the call as panicked if it is dropped while panicking, and the traits
+/// describing the arguments and the returned value with `Debug` if they implement it, and with
+/// `spy(serde)` serializing them if they implement `Serialize`, by autoref specialization.
+fn helpers(attr: &LetDefault, call_ident: &syn::Ident) -> TokenStream {
+    let guard_ident = guard_ident(call_ident);
+    let value_ident = format_ident!("{}Value", call_ident);
+    let debug_ident = format_ident!("{}Debug", call_ident);
+    let no_debug_ident = format_ident!("{}NoDebug", call_ident);
+    let (returned, serialize) = if attr.options.serde {
+        let serialize_ident = format_ident!("{}Serialize", call_ident);
+        let no_serialize_ident = format_ident!("{}NoSerialize", call_ident);
+        let returned = quote! {
+            fn returned(self, ret: Option<String>, ret_json: Option<::serde_json::Value>) {
+                let mut calls = self.calls.lock().unwrap();
+                calls[self.index].ret_json =
+                    ret_json.or_else(|| ret.clone().map(::serde_json::Value::String));
+                calls[self.index].ret = ret;
+            }
+        };
+        let serialize = quote! {
+            #[allow(non_camel_case_types)]
+            trait #serialize_ident {
+                fn to_json(&self) -> Option<::serde_json::Value>;
+            }
+
+            impl<T: ::serde::Serialize> #serialize_ident for #value_ident<'_, T> {
+                fn to_json(&self) -> Option<::serde_json::Value> {
+                    ::serde_json::to_value(self.0).ok()
+                }
+            }
+
+            #[allow(non_camel_case_types)]
+            trait #no_serialize_ident {
+                fn to_json(&self) -> Option<::serde_json::Value>;
+            }
+
+            impl<T> #no_serialize_ident for &#value_ident<'_, T> {
+                fn to_json(&self) -> Option<::serde_json::Value> {
+                    None
+                }
+            }
+        };
+        (returned, serialize)
+    } else {
+        let returned = quote! {
+            fn returned(self, ret: Option<String>) {
+                self.calls.lock().unwrap()[self.index].ret = ret;
+            }
+        };
+        (returned, quote!())
+    };
+    quote! {
+        #[allow(non_camel_case_types)]
+        struct #guard_ident<'a> {
+            calls: &'a std::sync::Mutex<Vec<#call_ident>>,
+            index: usize,
+        }
+
+        #[allow(non_camel_case_types)]
+        impl #guard_ident<'_> {
+            #returned
+        }
+
+        #[allow(non_camel_case_types)]
+        impl Drop for #guard_ident<'_> {
+            fn drop(&mut self) {
+                if std::thread::panicking() {
+                    let mut calls = self.calls.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+                    calls[self.index].panicked = true;
+                }
+            }
+        }
+
+        #[allow(non_camel_case_types)]
+        struct #value_ident<'a, T>(&'a T);
+
+        #[allow(non_camel_case_types)]
+        trait #debug_ident {
+            fn describe(&self) -> Option<String>;
+        }
+
+        impl<T: std::fmt::Debug> #debug_ident for #value_ident<'_, T> {
+            fn describe(&self) -> Option<String> {
+                Some(format!("{:?}", self.0))
+            }
+        }
+
+        #[allow(non_camel_case_types)]
+        trait #no_debug_ident {
+            fn describe(&self) -> Option<String>;
+        }
+
+        impl<T> #no_debug_ident for &#value_ident<'_, T> {
+            fn describe(&self) -> Option<String> {
+                None
+            }
+        }
+
+        #serialize
+    }
+}
+
+/// The statements recording the value returned by the dispatched expression, which becomes the
+/// last statement. The guard is bound by the prologue of the method.
+pub(crate) fn record_return(
+    attr: &LetDefault,
+    call_ident: &syn::Ident,
+    expr: syn::Expr,
+) -> Vec<syn::Stmt> {
+    let value_ident = format_ident!("{}Value", call_ident);
+    let ret = syn::Ident::new("ret", Span::mixed_site());
+    let guard = guard_local();
+    let ret_json = attr
+        .options
+        .serde
+        .then(|| quote!(, (&#value_ident(&#ret)).to_json()));
+    vec![
+        syn::parse_quote!(let #ret = #expr;),
+        syn::parse_quote!(#guard.returned((&#value_ident(&#ret)).describe() #ret_json);),
+        syn::Stmt::Expr(syn::parse_quote!(#ret), None),
+    ]
+}
+
+/// The statements recording the call and binding the guard of its record. The arguments which do
+/// not implement `Debug` are recorded as `_`.
+pub(crate) fn record_call(
+    attr: &LetDefault,
+    call_ident: &syn::Ident,
+    method_name: &str,
+    args: &[&syn::Ident],
+) -> Vec<syn::Stmt> {
+    let guard_ident = guard_ident(call_ident);
+    let value_ident = format_ident!("{}Value", call_ident);
+    let guard = guard_local();
+    let calls = syn::Ident::new("calls", Span::mixed_site());
+    let described = args
+        .iter()
+        .map(|arg| quote!((&#value_ident(&#arg)).describe().unwrap_or_else(|| "_".to_string())))
+        .collect::<Vec<_>>();
+    let json_fields = attr.options.serde.then(|| {
+        quote! {
+            args_json: vec![#(
+                (&#value_ident(&#args))
+                    .to_json()
+                    .unwrap_or_else(|| ::serde_json::Value::String(#described)),
+            )*],
+            ret_json: None,
+        }
+    });
+    vec![syn::parse_quote! {
+        let #guard = {
+            let mut #calls = self.__anonymous_trait_calls.lock().unwrap();
+            #calls.push(#call_ident {
+                method: #method_name,
+                args: vec![#(#described),*],
+                #json_fields
+                ret: None,
+                panicked: false,
+            });
+            #guard_ident {
+                calls: &self.__anonymous_trait_calls,
+                index: #calls.len() - 1,
+            }
+        };
+    }]
+}
+
+fn guard_ident(call_ident: &syn::Ident) -> syn::Ident {
+    format_ident!("{}Guard", call_ident)
+}
+
+/// The local holding the guard of the call record, which cannot collide with the arguments.
+fn guard_local() -> syn::Ident {
+    syn::Ident::new("call_guard", Span::mixed_site())
+}
+
+/// Methods serializing the recorded calls to JSON and comparing them with a golden file, which is
+/// written instead when the `ANONYMOUS_TRAIT_BLESS` environment variable is set.
+fn golden() -> TokenStream {
+    quote! {
+        fn calls_json(&self) -> String {
+            ::serde_json::to_string_pretty(&self.calls()).unwrap()
+        }
+
+        fn assert_calls_golden(&self, path: impl AsRef<std::path::Path>) {
+            let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+            let actual = self.calls_json() + "\n";
+            if std::env::var_os("ANONYMOUS_TRAIT_BLESS").is_some_and(|bless| !bless.is_empty()) {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).unwrap();
+                }
+                std::fs::write(&path, actual).unwrap();
+                return;
+            }
+            let expected = std::fs::read_to_string(&path).unwrap_or_else(|error| {
+                panic!(
+                    "cannot read the golden file {}: {}\nhelp: run with ANONYMOUS_TRAIT_BLESS=1 to create it",
+                    path.display(),
+                    error
+                )
+            });
+            if expected != actual {
+                let diff = expected
+                    .lines()
+                    .map(Some)
+                    .chain(std::iter::repeat(None))
+                    .zip(actual.lines().map(Some).chain(std::iter::repeat(None)))
+                    .take_while(|lines| lines != &(None, None))
+                    .map(|lines| match lines {
+                        (Some(expected), Some(actual)) if expected == actual => {
+                            format!("  {}\n", expected)
+                        }
+                        (expected, actual) => {
+                            let expected = expected.map(|line| format!("- {}\n", line));
+                            let actual = actual.map(|line| format!("+ {}\n", line));
+                            expected.unwrap_or_default() + &actual.unwrap_or_default()
+                        }
+                    })
+                    .collect::<String>();
+                panic!(
+                    "the calls do not match the golden file {}\n{}help: run with ANONYMOUS_TRAIT_BLESS=1 to update it",
+                    path.display(),
+                    diff
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn disabled() {
+        let attr = parse_quote! {
+            let my_mock = Cat
+        };
+        let input = parse_quote! {
+            impl Something for Cat {}
+        };
+        let actual = generate(&attr, &input);
+        assert!(actual.is_empty());
+    }
+
+    #[test]
+    fn enabled() {
+        let attr = parse_quote! {
+            let my_mock = Cat, spy
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self) -> String {
+                    "meow".to_string()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let helpers = helpers(&attr, &parse_quote!(my_mock__Something__Call));
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            #[derive(Debug, Clone, PartialEq, Eq)]
+            struct my_mock__Something__Call {
+                pub method: &'static str,
+                pub args: Vec<String>,
+                pub ret: Option<String>,
+                pub panicked: bool,
+            }
+
+            #helpers
+
+            #[allow(non_camel_case_types, dead_code)]
+            impl <
+                '__anonymous_trait_state,
+                meow: FnMut(&Cat) -> String,
+            > my_mock__Something<'__anonymous_trait_state, meow> {
+                fn calls(&self) -> Vec<my_mock__Something__Call> {
+                    self.__anonymous_trait_calls.lock().unwrap().clone()
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn serde() {
+        let mut attr: LetDefault = parse_quote! {
+            let my_mock = Cat, spy
+        };
+        // set directly, because parsing `spy(serde)` requires the feature
+        attr.options.serde = true;
+        let input = parse_quote! {
+            impl Something for Cat {}
+        };
+        let actual = generate(&attr, &input);
+        let helpers = helpers(&attr, &parse_quote!(my_mock__Something__Call));
+        let golden = golden();
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            #[derive(Debug, Clone, PartialEq, Eq, ::serde::Serialize)]
+            struct my_mock__Something__Call {
+                pub method: &'static str,
+                #[serde(skip)]
+                pub args: Vec<String>,
+                #[serde(rename = "args")]
+                pub args_json: Vec<::serde_json::Value>,
+                #[serde(skip)]
+                pub ret: Option<String>,
+                #[serde(rename = "ret")]
+                pub ret_json: Option<::serde_json::Value>,
+                pub panicked: bool,
+            }
+
+            #helpers
+
+            #[allow(non_camel_case_types, dead_code)]
+            impl <
+                '__anonymous_trait_state,
+            > my_mock__Something<'__anonymous_trait_state> {
+                fn calls(&self) -> Vec<my_mock__Something__Call> {
+                    self.__anonymous_trait_calls.lock().unwrap().clone()
+                }
+                #golden
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn record_return_value() {
+        let attr = parse_quote! {
+            let my_mock = Cat, spy
+        };
+        let actual = record_return(&attr, &parse_quote!(CatCall), parse_quote!(meow()));
+        let expected = quote! {
+            let ret = meow();
+            call_guard.returned((&CatCallValue(&ret)).describe());
+            ret
+        };
+        assert_eq!(quote!(#(#actual)*).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn record_serialized() {
+        let mut attr: LetDefault = parse_quote! {
+            let my_mock = Cat, spy
+        };
+        attr.options.serde = true;
+        let actual = record_return(&attr, &parse_quote!(CatCall), parse_quote!(meow()));
+        let expected = quote! {
+            let ret = meow();
+            call_guard.returned((&CatCallValue(&ret)).describe(), (&CatCallValue(&ret)).to_json());
+            ret
+        };
+        assert_eq!(quote!(#(#actual)*).to_string(), expected.to_string());
+        let actual = record_call(
+            &attr,
+            &parse_quote!(CatCall),
+            "feed",
+            &[&parse_quote!(food)],
+        );
+        let expected = quote! {
+            let call_guard = {
+                let mut calls = self.__anonymous_trait_calls.lock().unwrap();
+                calls.push(CatCall {
+                    method: "feed",
+                    args: vec![(&CatCallValue(&food)).describe().unwrap_or_else(|| "_".to_string())],
+                    args_json: vec![
+                        (&CatCallValue(&food))
+                            .to_json()
+                            .unwrap_or_else(|| ::serde_json::Value::String(
+                                (&CatCallValue(&food)).describe().unwrap_or_else(|| "_".to_string())
+                            )),
+                    ],
+                    ret_json: None,
+                    ret: None,
+                    panicked: false,
+                });
+                CatCallGuard {
+                    calls: &self.__anonymous_trait_calls,
+                    index: calls.len() - 1,
+                }
+            };
+        };
+        assert_eq!(quote!(#(#actual)*).to_string(), expected.to_string());
+    }
+}
@@ -0,0 +1,136 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+
+use crate::{attr_syntax::LetDefault, impl_syntax::AnonymousImpl};
+
+/// Generates, next to the trait, a `macro_rules!` with the same name as the trait that passes the
+/// trait definition to a callback macro. Importing the trait also imports the macro.
+pub(crate) fn generate(item_trait: &syn::ItemTrait) -> TokenStream {
+    let ident = &item_trait.ident;
+    let module = format_ident!("__anonymous_trait_{}", ident);
+    quote! {
+        #item_trait
+        #[doc(hidden)]
+        #[allow(non_snake_case)]
+        mod #module {
+            macro_rules! #ident {
+                ({ $($callback:tt)* } $($args:tt)*) => {
+                    $($callback)*! { { #item_trait } $($args)* }
+                };
+            }
+            pub(crate) use #ident;
+        }
+        #[allow(unused_imports)]
+        pub(crate) use #module::#ident;
+    }
+}
+
+/// Invokes the macro exported by `#[anonymous_trait::stubbable]` for the implemented trait, which
+/// expands to `__with_trait!` with the trait definition.
+pub(crate) fn request(trait_: &syn::Path, attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut macro_path = trait_.clone();
+    macro_path
+        .segments
+        .last_mut()
+        .expect("not empty trait path")
+        .arguments = syn::PathArguments::None;
+    quote! {
+        #macro_path! { { ::anonymous_trait::__with_trait } { #attr } { #item } }
+    }
+}
+
+/// The input of `__with_trait!`.
+pub(crate) struct WithTrait {
+    pub item_trait: syn::ItemTrait,
+    pub attr: LetDefault,
+    pub input: AnonymousImpl,
+}
+
+impl Parse for WithTrait {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let item_trait;
+        syn::braced!(item_trait in input);
+        let attr;
+        syn::braced!(attr in input);
+        let item;
+        syn::braced!(item in input);
+        Ok(Self {
+            item_trait: item_trait.parse()?,
+            attr: attr.parse()?,
+            input: item.parse()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn exports_trait() {
+        let item_trait = parse_quote! {
+            pub trait Cat {
+                fn meow(&self) -> String;
+            }
+        };
+        let actual = generate(&item_trait);
+        let expected = quote! {
+            pub trait Cat {
+                fn meow(&self) -> String;
+            }
+            #[doc(hidden)]
+            #[allow(non_snake_case)]
+            mod __anonymous_trait_Cat {
+                macro_rules! Cat {
+                    ({ $($callback:tt)* } $($args:tt)*) => {
+                        $($callback)*! {
+                            {
+                                pub trait Cat {
+                                    fn meow(&self) -> String;
+                                }
+                            }
+                            $($args)*
+                        }
+                    };
+                }
+                pub(crate) use Cat;
+            }
+            #[allow(unused_imports)]
+            pub(crate) use __anonymous_trait_Cat::Cat;
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn request_trait() {
+        let actual = request(
+            &parse_quote!(animal::Cat<u8>),
+            quote!(let my_mock = Cat, stub),
+            quote!(impl animal::Cat<u8> for Cat {}),
+        );
+        let expected = quote! {
+            animal::Cat! {
+                { ::anonymous_trait::__with_trait }
+                { let my_mock = Cat, stub }
+                { impl animal::Cat<u8> for Cat {} }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn parse_with_trait() {
+        let with_trait: WithTrait = parse_quote! {
+            { trait Something { fn meow(&self) -> String; } }
+            { let my_mock = Cat, stub }
+            { impl Something for Cat {} }
+        };
+        assert_eq!(with_trait.item_trait.ident, "Something");
+        assert_eq!(with_trait.attr.pat_ident.ident, "my_mock");
+        assert!(with_trait.input.trait_.is_ident("Something"));
+    }
+}
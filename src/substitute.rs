@@ -0,0 +1,119 @@
+use proc_macro2::{Group, TokenStream, TokenTree};
+use quote::{quote, ToTokens as _};
+
+/// Replaces `Self` and `Self::Name` with the given tokens, since closures are defined outside of
+/// the impl where `Self` has no meaning. `resolve` maps `Name` of `Self::Name` to its replacement
+/// and returns `None` to keep the path as `<Target>::Name`.
+pub(crate) fn replace_self_type(
+    tokens: TokenStream,
+    target: &syn::Type,
+    resolve: &dyn Fn(&syn::Ident) -> Option<TokenStream>,
+) -> TokenStream {
+    let mut output = TokenStream::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident == "Self" => {
+                let mut lookahead = tokens.clone();
+                let (path_sep, name) = match (lookahead.next(), lookahead.next(), lookahead.next())
+                {
+                    (
+                        Some(TokenTree::Punct(colon1)),
+                        Some(TokenTree::Punct(colon2)),
+                        Some(TokenTree::Ident(name)),
+                    ) if colon1.as_char() == ':' && colon2.as_char() == ':' => (true, Some(name)),
+                    (Some(TokenTree::Punct(colon1)), Some(TokenTree::Punct(colon2)), _)
+                        if colon1.as_char() == ':' && colon2.as_char() == ':' =>
+                    {
+                        (true, None)
+                    }
+                    _ => (false, None),
+                };
+                match name.and_then(|name| resolve(&name)) {
+                    Some(resolved) => {
+                        output.extend(resolved);
+                        tokens = lookahead;
+                    }
+                    // `<Target>::Name` is valid in both type and expression positions
+                    None if path_sep => output.extend(quote!(<#target>)),
+                    None => output.extend(target.to_token_stream()),
+                }
+            }
+            TokenTree::Group(group) => {
+                let mut replaced = Group::new(
+                    group.delimiter(),
+                    replace_self_type(group.stream(), target, resolve),
+                );
+                replaced.set_span(group.span());
+                output.extend([TokenTree::Group(replaced)]);
+            }
+            token => output.extend([token]),
+        }
+    }
+    output
+}
+
+/// Replaces the identifiers in `map` with the paired tokens, such as generic parameters of a trait
+/// with the arguments of the implemented trait path.
+pub(crate) fn replace_idents(
+    tokens: TokenStream,
+    map: &[(syn::Ident, TokenStream)],
+) -> TokenStream {
+    tokens
+        .into_iter()
+        .flat_map(|token| match token {
+            TokenTree::Ident(ident) => match map.iter().find(|(from, _)| from == &ident) {
+                Some((_, to)) => to.clone(),
+                None => ident.into_token_stream(),
+            },
+            TokenTree::Group(group) => {
+                let mut replaced =
+                    Group::new(group.delimiter(), replace_idents(group.stream(), map));
+                replaced.set_span(group.span());
+                TokenTree::Group(replaced).into_token_stream()
+            }
+            token => token.into_token_stream(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn self_type() {
+        let target = parse_quote!(Cat);
+        let actual = replace_self_type(
+            quote!(
+                fn merge(&self, other: Self) -> Vec<Self::Item> {
+                    Self::new()
+                }
+            ),
+            &target,
+            &|name| (name == "Item").then(|| quote!(u8)),
+        );
+        let expected = quote!(
+            fn merge(&self, other: Cat) -> Vec<u8> {
+                <Cat>::new()
+            }
+        );
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn idents() {
+        let actual = replace_idents(
+            quote!(fn get(&self, key: K) -> Option<Vec<V>>),
+            &[
+                (parse_quote!(K), quote!(String)),
+                (parse_quote!(V), quote!(u8)),
+            ],
+        );
+        let expected = quote!(fn get(&self, key: String) -> Option<Vec<u8>>);
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+}
@@ -0,0 +1,87 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use crate::{
+    attr_syntax::LetDefault,
+    impl_syntax::{cfg_attrs, AnonymousImpl},
+};
+
+/// Generates the `swap_{method}` setters replacing the closures of the methods.
+pub(crate) fn generate(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
+    if !attr.options.swappable {
+        return quote! {};
+    }
+    let struct_name = input.struct_name(attr);
+    let generics = crate::packed_struct::impl_generics(attr, input);
+    let where_clause = crate::packed_struct::where_clause(attr, input);
+    let struct_generics = crate::packed_struct::ty_generics(attr, input);
+    let setters = input.methods().map(|method| {
+        let method_ident = &method.sig.ident;
+        let setter_ident = setter_ident(method_ident);
+        let closure_type = crate::closure_type::generate(
+            &attr.options,
+            input.target(),
+            &input.closure_method(method),
+        );
+        let cfgs = cfg_attrs(&method.attrs);
+        quote! {
+            #(#cfgs)*
+            fn #setter_ident(
+                &self,
+                #method_ident: impl #closure_type + '__anonymous_trait_state,
+            ) {
+                *self.#method_ident.lock().unwrap() = Box::new(#method_ident);
+            }
+        }
+    });
+    quote! {
+        #[allow(non_camel_case_types, dead_code)]
+        impl <
+            #generics
+        > #struct_name<#struct_generics> #where_clause {
+            #(#setters)*
+        }
+    }
+}
+
+/// The name of the setter replacing the closure of the method.
+pub(crate) fn setter_ident(method: &syn::Ident) -> syn::Ident {
+    format_ident!("swap_{}", method)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn setters() {
+        let attr = parse_quote! {
+            let my_mock = Cat, swappable
+        };
+        let input = parse_quote! {
+            impl Something for Cat {
+                fn meow(&self, volume: u8) -> String {
+                    "meow".to_string()
+                }
+            }
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types, dead_code)]
+            impl <
+                '__anonymous_trait_state,
+            > my_mock__Something<'__anonymous_trait_state> {
+                fn swap_meow(
+                    &self,
+                    meow: impl FnMut(&Cat, u8) -> String + '__anonymous_trait_state,
+                ) {
+                    *self.meow.lock().unwrap() = Box::new(meow);
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+}
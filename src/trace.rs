@@ -0,0 +1,225 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+
+use crate::{attr_syntax::LetDefault, impl_syntax::AnonymousImpl};
+
+/// Generates the traits describing the arguments in the `tracing` events with `Debug` if they
+/// implement it, by autoref specialization.
+pub(crate) fn generate(attr: &LetDefault, input: &AnonymousImpl) -> TokenStream {
+    if !attr.options.tracing {
+        return quote! {};
+    }
+    let arg_ident = input.trace_arg_name(attr);
+    let debug_ident = format_ident!("{}Debug", arg_ident);
+    let no_debug_ident = format_ident!("{}NoDebug", arg_ident);
+    quote! {
+        #[allow(non_camel_case_types)]
+        struct #arg_ident<'a, T>(&'a T);
+
+        #[allow(non_camel_case_types)]
+        trait #debug_ident {
+            fn describe(&self) -> String;
+        }
+
+        impl<T: std::fmt::Debug> #debug_ident for #arg_ident<'_, T> {
+            fn describe(&self) -> String {
+                format!("{:?}", self.0)
+            }
+        }
+
+        #[allow(non_camel_case_types)]
+        trait #no_debug_ident {
+            fn describe(&self) -> String;
+        }
+
+        impl<T> #no_debug_ident for &#arg_ident<'_, T> {
+            fn describe(&self) -> String {
+                "_".to_string()
+            }
+        }
+    }
+}
+
+/// Runs the statements of a method in a `tracing` span for the call, after an event with its
+/// arguments. The span is entered in synchronous methods, and instruments the body of async
+/// methods, because a guard held across awaits would be entered on other tasks.
+pub(crate) fn instrument(
+    attr: &LetDefault,
+    input: &AnonymousImpl,
+    sig: &syn::Signature,
+    stmts: Vec<syn::Stmt>,
+) -> Vec<syn::Stmt> {
+    if !attr.options.tracing {
+        return stmts;
+    }
+    let trait_name = input
+        .trait_
+        .segments
+        .last()
+        .expect("not empty trait path")
+        .ident
+        .to_string();
+    let method_name = sig.ident.to_string();
+    let arg_ident = input.trace_arg_name(attr);
+    let args = crate::impl_trait::arg_pats(sig);
+    let fields = args.iter().map(|arg| format_ident!("{}", arg));
+    let new_span = quote! {
+        ::tracing::debug_span!(
+            "anonymous_trait",
+            trait_name = #trait_name,
+            method = #method_name,
+        )
+    };
+    let event: syn::Stmt = syn::parse_quote! {
+        ::tracing::debug!(
+            trait_name = #trait_name,
+            method = #method_name,
+            #(args.#fields = %(&#arg_ident(&#args)).describe(),)*
+            "call of an anonymous trait method"
+        );
+    };
+    if sig.asyncness.is_some() {
+        return vec![syn::Stmt::Expr(
+            syn::parse_quote! {
+                ::tracing::Instrument::instrument(
+                    async move {
+                        #event
+                        #(#stmts)*
+                    },
+                    #new_span,
+                )
+                .await
+            },
+            None,
+        )];
+    }
+    let span = syn::Ident::new("span", Span::mixed_site());
+    let mut traced = vec![syn::parse_quote!(let #span = #new_span.entered();), event];
+    traced.extend(stmts);
+    traced
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    use super::*;
+
+    fn traced(options: &str) -> LetDefault {
+        let mut attr: LetDefault = syn::parse_str(options).unwrap();
+        // set directly, because parsing the option requires the feature
+        attr.options.tracing = true;
+        attr
+    }
+
+    #[test]
+    fn disabled() {
+        let attr = parse_quote! {
+            let my_mock = Cat
+        };
+        let input = parse_quote! {
+            impl Something for Cat {}
+        };
+        assert!(generate(&attr, &input).is_empty());
+        let stmts = vec![parse_quote!(feed();)];
+        let actual = instrument(&attr, &input, &parse_quote!(fn feed(&self)), stmts.clone());
+        assert_eq!(actual, stmts);
+    }
+
+    #[test]
+    fn span_and_event() {
+        let attr = traced("let my_mock = Cat");
+        let input = parse_quote! {
+            impl Something for Cat {}
+        };
+        let sig = parse_quote! {
+            fn feed(&mut self, food: &str, grams: u32)
+        };
+        let actual = instrument(&attr, &input, &sig, vec![parse_quote!(eat(food, grams);)]);
+        let expected = quote! {
+            let span = ::tracing::debug_span!(
+                "anonymous_trait",
+                trait_name = "Something",
+                method = "feed",
+            )
+            .entered();
+            ::tracing::debug!(
+                trait_name = "Something",
+                method = "feed",
+                args.food = %(&my_mock__Something__TraceArg(&food)).describe(),
+                args.grams = %(&my_mock__Something__TraceArg(&grams)).describe(),
+                "call of an anonymous trait method"
+            );
+            eat(food, grams);
+        };
+        assert_eq!(quote!(#(#actual)*).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn instrument_async_method() {
+        let attr = traced("let my_mock = Cat, as CatMock");
+        let input = parse_quote! {
+            impl Something for Cat {}
+        };
+        let sig = parse_quote! {
+            async fn meow(&self)
+        };
+        let actual = instrument(&attr, &input, &sig, vec![parse_quote!(purr().await;)]);
+        let expected = quote! {
+            ::tracing::Instrument::instrument(
+                async move {
+                    ::tracing::debug!(
+                        trait_name = "Something",
+                        method = "meow",
+                        "call of an anonymous trait method"
+                    );
+                    purr().await;
+                },
+                ::tracing::debug_span!(
+                    "anonymous_trait",
+                    trait_name = "Something",
+                    method = "meow",
+                ),
+            )
+            .await
+        };
+        assert_eq!(quote!(#(#actual)*).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn describe_arguments() {
+        let attr = traced("let my_mock = Cat, as CatMock");
+        let input = parse_quote! {
+            impl Something for Cat {}
+        };
+        let actual = generate(&attr, &input);
+        let expected = quote! {
+            #[allow(non_camel_case_types)]
+            struct CatMockTraceArg<'a, T>(&'a T);
+
+            #[allow(non_camel_case_types)]
+            trait CatMockTraceArgDebug {
+                fn describe(&self) -> String;
+            }
+
+            impl<T: std::fmt::Debug> CatMockTraceArgDebug for CatMockTraceArg<'_, T> {
+                fn describe(&self) -> String {
+                    format!("{:?}", self.0)
+                }
+            }
+
+            #[allow(non_camel_case_types)]
+            trait CatMockTraceArgNoDebug {
+                fn describe(&self) -> String;
+            }
+
+            impl<T> CatMockTraceArgNoDebug for &CatMockTraceArg<'_, T> {
+                fn describe(&self) -> String {
+                    "_".to_string()
+                }
+            }
+        };
+        assert_eq!(actual.to_string(), expected.to_string());
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Mutex,
+    task::{Context, Poll},
+};
+
+trait Cat {
+    async fn meow(&self, id: u32) -> String;
+}
+
+#[tokio::test]
+async fn step_in_order_of_arrival() {
+    let log = Mutex::new(Vec::new());
+
+    #[anonymous_trait::anonymous_trait(let cat_mock = (), meow: gated)]
+    impl Cat for () {
+        async fn meow(&self, id: u32) -> String {
+            format!("meow {}", id)
+        }
+    }
+
+    let call = |id| {
+        let cat_mock = &cat_mock;
+        let log = &log;
+        async move {
+            let meow = cat_mock.meow(id).await;
+            log.lock().unwrap().push(meow);
+        }
+    };
+    let test = async {
+        let gate = cat_mock.meow_gate();
+        gate.until_waiting(3).await;
+        assert_eq!(gate.passed(), 0);
+        for passed in 1..=3 {
+            gate.step();
+            gate.until_passed(passed).await;
+            assert_eq!(gate.waiting(), 3 - passed);
+        }
+    };
+    tokio::join!(call(1), call(2), call(3), test);
+    assert_eq!(*log.lock().unwrap(), vec!["meow 1", "meow 2", "meow 3"]);
+}
+
+#[tokio::test]
+async fn cancelled_call_gives_back_its_ticket() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = (), meow: gated)]
+    impl Cat for () {
+        async fn meow(&self, id: u32) -> String {
+            format!("meow {}", id)
+        }
+    }
+
+    let gate = cat_mock.meow_gate();
+    {
+        let mut cancelled = pin!(cat_mock.meow(1));
+        let pending = std::future::poll_fn(|cx: &mut Context| {
+            Poll::Ready(cancelled.as_mut().poll(cx).is_pending())
+        })
+        .await;
+        assert!(pending);
+        assert_eq!(gate.waiting(), 1);
+    }
+    assert_eq!(gate.waiting(), 0);
+
+    let test = async {
+        gate.until_waiting(1).await;
+        gate.step();
+    };
+    let (meow, ()) = tokio::join!(cat_mock.meow(2), test);
+    assert_eq!(meow, "meow 2");
+    assert_eq!(gate.passed(), 1);
+    assert_eq!(gate.waiting(), 0);
+}
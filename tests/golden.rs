@@ -0,0 +1,85 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+trait Cat {
+    fn meow(&self) -> String;
+    fn feed(&mut self, food: &str, grams: u32);
+    fn groom(&self, brush: Brush);
+}
+
+// does not implement `Serialize`, so it is serialized as its `Debug` representation
+#[derive(Debug)]
+struct Brush;
+
+fn panic_message(run: impl FnOnce()) -> String {
+    let payload = catch_unwind(AssertUnwindSafe(run)).unwrap_err();
+    payload.downcast_ref::<String>().unwrap().clone()
+}
+
+// A single test, because blessing sets an environment variable of the process.
+#[test]
+fn golden_file() {
+    #[anonymous_trait::anonymous_trait(let mut cat_mock = 0u32, spy(serde))]
+    impl Cat for u32 {
+        fn meow(&self) -> String {
+            format!("meow after eating {}g", self)
+        }
+
+        fn feed(&mut self, food: &str, grams: u32) {
+            let _ = food;
+            *self += grams;
+        }
+
+        fn groom(&self, _brush: Brush) {}
+    }
+
+    cat_mock.feed("fish", 30);
+    cat_mock.meow();
+    cat_mock.groom(Brush);
+    let json = cat_mock.calls_json();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+        serde_json::json!([
+            {
+                "method": "feed",
+                "args": ["fish", 30],
+                "ret": null,
+                "panicked": false,
+            },
+            {
+                "method": "meow",
+                "args": [],
+                "ret": "meow after eating 30g",
+                "panicked": false,
+            },
+            {
+                "method": "groom",
+                "args": ["Brush"],
+                "ret": null,
+                "panicked": false,
+            },
+        ])
+    );
+
+    let dir = std::env::temp_dir().join(format!("anonymous-trait-golden-{}", std::process::id()));
+    let path = dir.join("calls.json");
+    let message = panic_message(|| cat_mock.assert_calls_golden(&path));
+    assert!(message.starts_with("cannot read the golden file"));
+    assert!(message.ends_with("help: run with ANONYMOUS_TRAIT_BLESS=1 to create it"));
+
+    std::env::set_var("ANONYMOUS_TRAIT_BLESS", "1");
+    cat_mock.assert_calls_golden(&path);
+    std::env::remove_var("ANONYMOUS_TRAIT_BLESS");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), json.clone() + "\n");
+    cat_mock.assert_calls_golden(&path);
+
+    std::fs::write(&path, json.replace("30", "40") + "\n").unwrap();
+    let message = panic_message(|| cat_mock.assert_calls_golden(&path));
+    assert!(message.starts_with(&format!(
+        "the calls do not match the golden file {}\n",
+        path.display()
+    )));
+    assert!(message.contains("\n        \"fish\",\n-       40\n+       30\n"));
+    assert!(message.ends_with("help: run with ANONYMOUS_TRAIT_BLESS=1 to update it"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,22 @@
+trait Named {
+    fn name(&self) -> String;
+    fn swap_name(&self, other: &mut String);
+    fn name_call_count(&self) -> usize;
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let named = String::new(), swappable, spy)]
+    impl Named for String {
+        fn name(&self) -> String {
+            self.clone()
+        }
+
+        fn swap_name(&self, other: &mut String) {
+            *other = self.clone();
+        }
+
+        fn name_call_count(&self) -> usize {
+            0
+        }
+    }
+}
@@ -0,0 +1,14 @@
+trait Cat {
+    fn move_to(&mut self, position: (i32, i32));
+    fn ignore(&self, volume: u8);
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = (0, 0))]
+    impl Cat for (i32, i32) {
+        fn move_to(&mut self, (x, y): (i32, i32)) {
+            *self = (x, y);
+        }
+        fn ignore(&self, _: u8) {}
+    }
+}
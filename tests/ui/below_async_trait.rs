@@ -0,0 +1,16 @@
+use async_trait::async_trait;
+
+#[async_trait]
+trait Cat {
+    async fn meow(&self) -> String;
+}
+
+fn main() {
+    #[async_trait]
+    #[anonymous_trait::anonymous_trait(let cat_mock = ())]
+    impl Cat for () {
+        async fn meow(&self) -> String {
+            "meow".into()
+        }
+    }
+}
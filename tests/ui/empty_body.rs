@@ -0,0 +1,12 @@
+trait Cat {
+    fn meow(&self) -> String;
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = ())]
+    impl Cat for () {
+        fn meow(&self) -> String {}
+    }
+
+    let _ = cat_mock.meow();
+}
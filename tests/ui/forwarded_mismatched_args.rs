@@ -0,0 +1,20 @@
+trait Cat {
+    fn meow(&self, volume: u8) -> String;
+}
+
+struct RealCat;
+
+impl Cat for RealCat {
+    fn meow(&self, _volume: u8) -> String {
+        "meow".into()
+    }
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = RealCat, wrap)]
+    impl Cat for RealCat {
+        fn meow(&self, volume: u16) -> String;
+    }
+
+    let _ = cat_mock.meow(1);
+}
@@ -0,0 +1,15 @@
+trait Cat {
+    fn eat<F: Food>(&self, food: F);
+    fn play(&self, toy: impl Toy);
+}
+
+trait Food {}
+trait Toy {}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = ())]
+    impl Cat for () {
+        fn eat<F: Food>(&self, food: F) {}
+        fn play(&self, toy: impl Toy) {}
+    }
+}
@@ -0,0 +1,18 @@
+trait Cat {
+    fn meow(&self) -> String;
+}
+
+macro_rules! meow {
+    () => {
+        fn meow(&self) -> String {
+            "meow".into()
+        }
+    };
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = ())]
+    impl Cat for () {
+        meow!();
+    }
+}
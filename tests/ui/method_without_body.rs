@@ -0,0 +1,10 @@
+trait Cat {
+    fn meow(&self) -> String;
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = ())]
+    impl Cat for () {
+        fn meow(&self) -> String;
+    }
+}
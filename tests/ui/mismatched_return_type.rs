@@ -0,0 +1,14 @@
+trait Cat {
+    fn meow(&self) -> String;
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = ())]
+    impl Cat for () {
+        fn meow(&self) -> String {
+            42
+        }
+    }
+
+    let _ = cat_mock.meow();
+}
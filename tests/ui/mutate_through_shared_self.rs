@@ -0,0 +1,14 @@
+trait Cat {
+    fn rename(&self, name: String);
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = String::new())]
+    impl Cat for String {
+        fn rename(&self, name: String) {
+            *self = name;
+        }
+    }
+
+    cat_mock.rename("cat".into());
+}
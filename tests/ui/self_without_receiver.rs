@@ -0,0 +1,12 @@
+trait Cat {
+    fn new_name() -> String;
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = String::new())]
+    impl Cat for String {
+        fn new_name() -> String {
+            self.clone()
+        }
+    }
+}
@@ -0,0 +1,18 @@
+struct State {
+    name: String,
+}
+
+trait Cat {
+    fn meow(&self) -> String;
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = State { name: "cat".into() })]
+    impl Cat for State {
+        fn meow(&self) -> String {
+            self.nickname.clone()
+        }
+    }
+
+    let _ = cat_mock.meow();
+}
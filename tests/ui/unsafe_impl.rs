@@ -0,0 +1,12 @@
+unsafe trait Cat {
+    fn meow(&self) -> String;
+}
+
+fn main() {
+    #[anonymous_trait::anonymous_trait(let cat_mock = ())]
+    unsafe impl Cat for () {
+        fn meow(&self) -> String {
+            "meow".into()
+        }
+    }
+}